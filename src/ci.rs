@@ -0,0 +1,84 @@
+// Copyright (C) 2026 Leandro Lisboa Penz <lpenz@lpenz.org>
+// This file is subject to the terms and conditions defined in
+// file 'LICENSE', which is part of this source code package.
+
+//! CI providers other than github, whose variables are translated
+//! into the github ones
+
+use crate::Annotation;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    #[default]
+    GitHub,
+    GitLab,
+}
+
+fn var<'a>(vars: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    vars.iter()
+        .rev()
+        .find(|(k, _)| k == name)
+        .map(|(_, v)| v.as_str())
+        .filter(|v| !v.is_empty())
+}
+
+impl Provider {
+    pub fn detect(vars: &[(String, String)]) -> Provider {
+        if var(vars, "GITLAB_CI") == Some("true") {
+            Provider::GitLab
+        } else {
+            Provider::GitHub
+        }
+    }
+
+    /// The github variables that correspond to the provider's ones
+    pub fn github_vars(&self, vars: &[(String, String)]) -> Vec<(String, String)> {
+        let mut github = vec![];
+        let mut push = |k: &str, v: String| github.push((k.to_string(), v));
+        match self {
+            Provider::GitHub => {}
+            Provider::GitLab => {
+                if let Some(source) = var(vars, "CI_PIPELINE_SOURCE") {
+                    let event = match source {
+                        "merge_request_event" => "pull_request",
+                        "web" | "api" => "workflow_dispatch",
+                        s => s,
+                    };
+                    push("GITHUB_EVENT_NAME", event.to_string());
+                }
+                if let Some(tag) = var(vars, "CI_COMMIT_TAG") {
+                    push("GITHUB_REF", format!("refs/tags/{tag}"));
+                } else if let Some(branch) = var(vars, "CI_COMMIT_BRANCH") {
+                    push("GITHUB_REF", format!("refs/heads/{branch}"));
+                } else if let Some(iid) = var(vars, "CI_MERGE_REQUEST_IID") {
+                    push("GITHUB_REF", format!("refs/pull/{iid}/merge"));
+                }
+                if let Some(head) = var(vars, "CI_MERGE_REQUEST_SOURCE_BRANCH_NAME") {
+                    push("GITHUB_HEAD_REF", head.to_string());
+                }
+                if let Some(base) = var(vars, "CI_MERGE_REQUEST_TARGET_BRANCH_NAME") {
+                    push("GITHUB_BASE_REF", base.to_string());
+                }
+                if let Some(branch) = var(vars, "CI_DEFAULT_BRANCH") {
+                    push("VERSION_GEN_DEFAULT_BRANCH", branch.to_string());
+                }
+            }
+        }
+        github
+    }
+
+    /// Reports the annotation in the way the provider understands
+    pub fn annotation(&self, annotation: &Annotation, level: &str) -> String {
+        match self {
+            Provider::GitHub => annotation.command(level),
+            Provider::GitLab => {
+                let location = match (&annotation.file, annotation.position) {
+                    (Some(file), Some(p)) => format!("{}:{}:{}: ", file.display(), p.line, p.col),
+                    (Some(file), None) => format!("{}: ", file.display()),
+                    _ => String::new(),
+                };
+                format!("{location}{level}: {annotation}")
+            }
+        }
+    }
+}
@@ -0,0 +1,115 @@
+// Copyright (C) 2026 Leandro Lisboa Penz <lpenz@lpenz.org>
+// This file is subject to the terms and conditions defined in
+// file 'LICENSE', which is part of this source code package.
+
+//! Project configuration, read from `.github/version-gen.toml`
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::path::PathBuf;
+
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+
+use serde::Deserialize;
+
+/// Default location of the configuration file, relative to the
+/// repository
+pub const CONFIG_PATH: &str = ".github/version-gen.toml";
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    #[serde(default)]
+    pub tags: Tags,
+    #[serde(default)]
+    pub branches: Branches,
+    #[serde(default)]
+    pub manifests: Manifests,
+    #[serde(default)]
+    pub outputs: Outputs,
+    #[serde(default)]
+    pub on_mismatch: MismatchPolicy,
+}
+
+/// Tags considered by `git describe`
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Tags {
+    pub include: Option<Vec<String>>,
+    pub exclude: Option<Vec<String>>,
+    pub versions_only: Option<bool>,
+    /// Component of a monorepo, with tags like `<component>/v1.0.0`
+    pub component: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Branches {
+    pub main: Option<Vec<String>>,
+    pub release: Option<Vec<String>>,
+    /// Name of the `workflow_dispatch` input with the version
+    pub dispatch_input: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Manifests {
+    /// Directory of the manifests, relative to the repository
+    pub dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Outputs {
+    /// Only emit these outputs; all of them if not given
+    pub only: Option<Vec<String>>,
+    /// Outputs exported as environment variables to the next steps
+    pub env: Option<Vec<String>>,
+    /// Prefix of the exported environment variables
+    pub env_prefix: Option<String>,
+    /// Write the summary of the run [default: true]
+    pub step_summary: Option<bool>,
+}
+
+/// What to do when the versions in the files don't match the tag
+/// being released
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MismatchPolicy {
+    /// Report errors and fail
+    #[default]
+    Fail,
+    /// Report warnings only
+    Warn,
+    /// Don't report anything
+    Ignore,
+}
+
+impl Config {
+    pub fn parse(contents: &str) -> Result<Config> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Config> {
+        let path = path.as_ref();
+        let contents =
+            fs::read_to_string(path).map_err(|e| eyre!("reading {}: {}", path.display(), e))?;
+        Config::parse(&contents).map_err(|e| eyre!("parsing {}: {}", path.display(), e))
+    }
+
+    /// Reads the given configuration file or, if none was given, the
+    /// one in the default location of the repository, if it exists.
+    pub fn load<P: AsRef<Path>>(repo: P, path: Option<&Path>) -> Result<Config> {
+        if let Some(path) = path {
+            return Config::read(path);
+        }
+        let path = repo.as_ref().join(CONFIG_PATH);
+        match fs::metadata(&path) {
+            Ok(_) => Config::read(path),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
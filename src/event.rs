@@ -0,0 +1,93 @@
+// Copyright (C) 2026 Leandro Lisboa Penz <lpenz@lpenz.org>
+// This file is subject to the terms and conditions defined in
+// file 'LICENSE', which is part of this source code package.
+
+//! Payload of the github event, in the file given by
+//! `GITHUB_EVENT_PATH`
+
+use std::fs;
+use std::path::Path;
+
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+
+use serde_json::Value;
+
+/// The fields of the payload that we use; all of them are optional,
+/// as each kind of event has a different set.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub created: Option<bool>,
+    pub deleted: Option<bool>,
+    pub forced: Option<bool>,
+    /// Activity that triggered the event, e.g. `published` for releases
+    pub action: Option<String>,
+    /// Names of the labels of the pull request
+    pub labels: Vec<String>,
+    pub release_prerelease: Option<bool>,
+    pub release_draft: Option<bool>,
+    /// Inputs of a `workflow_dispatch`, converted to strings
+    pub inputs: Vec<(String, String)>,
+}
+
+fn string(value: &Value, key: &str) -> Option<String> {
+    value.get(key).and_then(Value::as_str).map(String::from)
+}
+
+fn boolean(value: &Value, key: &str) -> Option<bool> {
+    value.get(key).and_then(Value::as_bool)
+}
+
+fn array<'a>(value: &'a Value, key: &str) -> impl Iterator<Item = &'a Value> {
+    value
+        .get(key)
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+}
+
+impl Event {
+    pub fn parse(contents: &str) -> Result<Event> {
+        let json: Value =
+            serde_json::from_str(contents).map_err(|e| eyre!("parsing event payload: {}", e))?;
+        let mut event = Event {
+            created: boolean(&json, "created"),
+            deleted: boolean(&json, "deleted"),
+            forced: boolean(&json, "forced"),
+            action: string(&json, "action"),
+            ..Event::default()
+        };
+        if let Some(pull_request) = json.get("pull_request") {
+            event.labels = array(pull_request, "labels")
+                .filter_map(|l| string(l, "name"))
+                .collect();
+        }
+        if let Some(release) = json.get("release") {
+            event.release_prerelease = boolean(release, "prerelease");
+            event.release_draft = boolean(release, "draft");
+        }
+        if let Some(inputs) = json.get("inputs").and_then(Value::as_object) {
+            for (k, v) in inputs {
+                let v = match v {
+                    Value::String(s) => s.clone(),
+                    Value::Null => String::new(),
+                    v => v.to_string(),
+                };
+                event.inputs.push((k.clone(), v));
+            }
+        }
+        Ok(event)
+    }
+
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Event> {
+        Event::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Value of a `workflow_dispatch` input
+    pub fn input(&self, name: &str) -> Option<&str> {
+        self.inputs
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
@@ -2,42 +2,244 @@
 // This file is subject to the terms and conditions defined in
 // file 'LICENSE', which is part of this source code package.
 
+//! Git queries, done in-process when the `gix` feature is enabled,
+//! with the `git` binary as a fallback.
+
 use std::path::Path;
-use std::process::Command;
 
 use color_eyre::Result;
-use color_eyre::eyre::Report;
-use color_eyre::eyre::ensure;
-
-pub fn run<P: AsRef<Path>>(repo: P, args: &[&str]) -> Result<String> {
-    let result = Command::new("git")
-        .current_dir(repo.as_ref())
-        .args(args)
-        .output()?;
-    ensure!(
-        result.status.success(),
-        "error running git {:?}: {:?}; in {:?}",
-        args,
-        result,
-        repo.as_ref().display(),
-    );
-    String::from_utf8(result.stdout)
-        .map(|s| s.trim().to_string())
-        .map_err(Report::from)
-}
-
-pub fn describe<P: AsRef<Path>>(repo: P) -> Result<String> {
-    run(repo, &["describe", "--tags"])
+
+use regex::Regex;
+
+pub mod command;
+#[cfg(feature = "gix")]
+pub mod native;
+
+/// Selects the tags that `describe` considers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagFilter {
+    /// Prefix that all tags must have, e.g. `api/` for `api/v1.0.0`
+    pub prefix: String,
+    /// Globs of the tags to consider; all tags if empty
+    pub include: Vec<String>,
+    /// Globs of the tags to ignore
+    pub exclude: Vec<String>,
+    /// Only consider tags that look like versions, e.g. `v1.2.3`
+    pub versions_only: bool,
+}
+
+impl Default for TagFilter {
+    fn default() -> Self {
+        TagFilter {
+            prefix: String::new(),
+            include: vec![],
+            exclude: vec![],
+            versions_only: true,
+        }
+    }
+}
+
+pub fn glob_matches(pattern: &str, tag: &str) -> bool {
+    glob::Pattern::new(pattern).is_ok_and(|p| p.matches(tag))
+}
+
+/// True for tags like `v1`, `1.2` or `v1.2.3-rc.1+build`
+pub fn looks_like_version(tag: &str) -> bool {
+    let re = Regex::new(r"^v?\d+(\.\d+)*([-+][0-9A-Za-z.+-]*)?$").unwrap();
+    re.is_match(tag)
+}
+
+impl TagFilter {
+    /// Checks the tag against the filter; the globs match the whole
+    /// tag, while `versions_only` only looks at what's after the
+    /// prefix.
+    pub fn matches(&self, tag: &str) -> bool {
+        let Some(version) = tag.strip_prefix(&self.prefix) else {
+            return false;
+        };
+        (self.include.is_empty() || self.include.iter().any(|p| glob_matches(p, tag)))
+            && !self.exclude.iter().any(|p| glob_matches(p, tag))
+            && (!self.versions_only || looks_like_version(version))
+    }
+}
+
+pub fn describe<P: AsRef<Path>>(repo: P, filter: &TagFilter) -> Result<String> {
+    #[cfg(feature = "gix")]
+    if let Ok(description) = native::describe(&repo, filter) {
+        return Ok(description);
+    }
+    command::describe(repo, filter)
+}
+
+/// All the tags that pass the filter
+pub fn tags<P: AsRef<Path>>(repo: P, filter: &TagFilter) -> Result<Vec<String>> {
+    #[cfg(feature = "gix")]
+    if let Ok(tags) = native::tags(&repo, filter) {
+        return Ok(tags);
+    }
+    command::tags(repo, filter)
+}
+
+/// Why `describe` found a tag or not
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagStatus {
+    Found,
+    /// There are no tags reachable from HEAD
+    NoTags,
+    /// The clone is shallow and the tag may be in the missing history
+    Shallow,
+    /// The remote has tags that were not fetched
+    NotFetched,
+    /// The repository can't be read
+    NoGit,
+}
+
+impl TagStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TagStatus::Found => "found",
+            TagStatus::NoTags => "no-tags",
+            TagStatus::Shallow => "shallow",
+            TagStatus::NotFetched => "not-fetched",
+            TagStatus::NoGit => "no-git",
+        }
+    }
+}
+
+/// Finds out why `describe` didn't find a tag; the remote is only
+/// queried if `remote` is set.
+pub fn tag_status<P: AsRef<Path>>(repo: P, filter: &TagFilter, remote: bool) -> TagStatus {
+    match is_shallow(&repo) {
+        Err(_) => TagStatus::NoGit,
+        Ok(true) => TagStatus::Shallow,
+        Ok(false) => {
+            if remote
+                && tags(&repo, filter).is_ok_and(|t| t.is_empty())
+                && command::remote_tags(&repo, filter).is_ok_and(|t| !t.is_empty())
+            {
+                TagStatus::NotFetched
+            } else {
+                TagStatus::NoTags
+            }
+        }
+    }
+}
+
+/// Name of a local or remote branch, without the remote
+pub fn branch_name(refname: &str) -> Option<&str> {
+    if let Some(name) = refname.strip_prefix("refs/heads/") {
+        return Some(name);
+    }
+    let (_remote, name) = refname.strip_prefix("refs/remotes/")?.split_once('/')?;
+    (name != "HEAD").then_some(name)
+}
+
+/// Name of the checked out branch; fails if HEAD is detached
+pub fn head_branch<P: AsRef<Path>>(repo: P) -> Result<String> {
+    #[cfg(feature = "gix")]
+    if let Ok(branch) = native::head_branch(&repo) {
+        return Ok(branch);
+    }
+    command::head_branch(repo)
+}
+
+/// Names of the local and remote branches that point to HEAD
+pub fn head_branches<P: AsRef<Path>>(repo: P) -> Result<Vec<String>> {
+    #[cfg(feature = "gix")]
+    if let Ok(branches) = native::head_branches(&repo) {
+        return Ok(branches);
+    }
+    command::head_branches(repo)
 }
 
 pub fn ref_commit<P: AsRef<Path>>(repo: P, reference: &str) -> Result<String> {
-    run(repo, &["rev-parse", "--short", reference])
+    #[cfg(feature = "gix")]
+    if let Ok(commit) = native::ref_commit(&repo, reference) {
+        return Ok(commit);
+    }
+    command::ref_commit(repo, reference)
 }
 
 pub fn head_commit<P: AsRef<Path>>(repo: P) -> Result<String> {
     ref_commit(repo, "HEAD")
 }
 
-pub fn unshallow<P: AsRef<Path>>(repo: P) -> Result<String> {
-    run(repo, &["fetch", "--unshallow", "origin"])
+pub fn is_shallow<P: AsRef<Path>>(repo: P) -> Result<bool> {
+    #[cfg(feature = "gix")]
+    if let Ok(shallow) = native::is_shallow(&repo) {
+        return Ok(shallow);
+    }
+    command::is_shallow(repo)
+}
+
+/// Commits fetched in each step when deepening a shallow clone
+const DEEPEN_STEPS: &[u32] = &[50, 200, 1000];
+
+/// Fetches the tags and the tips of the trunk and release branches
+/// that the checkout didn't fetch. A fetch with `--depth` cuts the
+/// history at the commits it brings, so the refs that point to
+/// commits we already have are fetched without it, and only the
+/// others are fetched with just their commit.
+fn fetch_refs<P: AsRef<Path>>(
+    repo: P,
+    filter: &TagFilter,
+    main: &[String],
+    release: &[String],
+) -> Result<()> {
+    let refs = command::remote_refs(&repo)?
+        .into_iter()
+        .filter_map(|(name, commit)| {
+            let refspec = if let Some(tag) = name.strip_prefix("refs/tags/") {
+                filter.matches(tag).then(|| format!("+{name}:{name}"))
+            } else {
+                let branch = branch_name(&name)?;
+                let tracking = format!("refs/remotes/origin/{branch}");
+                let wanted = main.iter().any(|m| m == branch)
+                    || release.iter().any(|p| glob_matches(p, branch));
+                (wanted && ref_commit(&repo, &tracking).is_err())
+                    .then(|| format!("+{name}:{tracking}"))
+            }?;
+            Some((refspec, commit))
+        })
+        .collect::<Vec<_>>();
+    let commits = refs.iter().map(|(_, c)| c.as_str()).collect::<Vec<_>>();
+    let present = command::has_objects(&repo, &commits)?;
+    let (present, missing): (Vec<_>, Vec<_>) =
+        refs.iter().zip(present).partition(|(_, present)| *present);
+    for (depth, refs) in [(None, present), (Some("--depth=1"), missing)] {
+        if refs.is_empty() {
+            continue;
+        }
+        let mut args = vec!["fetch"];
+        args.extend(depth);
+        args.push("origin");
+        args.extend(refs.iter().map(|((refspec, _), _)| refspec.as_str()));
+        command::run(&repo, &args)?;
+    }
+    Ok(())
+}
+
+/// Fetches just enough history for `describe` to find the most recent
+/// tag: the tags and branch tips first, as they are usually not
+/// fetched by the checkout, and then the commits, in steps. The
+/// repository may still be shallow in the end, which `tag_status`
+/// reports if no tag was found.
+pub fn fetch_history<P: AsRef<Path>>(
+    repo: P,
+    filter: &TagFilter,
+    main: &[String],
+    release: &[String],
+) -> Result<()> {
+    if !is_shallow(&repo)? {
+        return Ok(());
+    }
+    // Network problems are caught by the deepening below
+    let _ = fetch_refs(&repo, filter, main, release);
+    for step in DEEPEN_STEPS {
+        if describe(&repo, filter).is_ok() || !is_shallow(&repo)? {
+            return Ok(());
+        }
+        command::run(&repo, &["fetch", &format!("--deepen={step}"), "origin"])?;
+    }
+    Ok(())
 }
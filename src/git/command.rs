@@ -0,0 +1,153 @@
+// Copyright (C) 2021 Leandro Lisboa Penz <lpenz@lpenz.org>
+// This file is subject to the terms and conditions defined in
+// file 'LICENSE', which is part of this source code package.
+
+//! Backend that runs the `git` binary
+
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+use std::process::Stdio;
+use std::thread;
+
+use color_eyre::Result;
+use color_eyre::eyre::Report;
+use color_eyre::eyre::ensure;
+
+use super::TagFilter;
+
+pub fn run<P: AsRef<Path>>(repo: P, args: &[&str]) -> Result<String> {
+    let result = Command::new("git")
+        .current_dir(repo.as_ref())
+        .args(args)
+        .output()?;
+    ensure!(
+        result.status.success(),
+        "error running git {:?}: {:?}; in {:?}",
+        args,
+        result,
+        repo.as_ref().display(),
+    );
+    String::from_utf8(result.stdout)
+        .map(|s| s.trim().to_string())
+        .map_err(Report::from)
+}
+
+pub fn describe<P: AsRef<Path>>(repo: P, filter: &TagFilter) -> Result<String> {
+    let mut args = vec!["describe".to_string(), "--tags".to_string()];
+    for pattern in &filter.include {
+        args.push(format!("--match={pattern}"));
+    }
+    for pattern in &filter.exclude {
+        args.push(format!("--exclude={pattern}"));
+    }
+    if filter.versions_only || !filter.prefix.is_empty() {
+        // git only filters with globs, so we have to exclude each one
+        // of the tags that don't look like versions or don't have the
+        // prefix:
+        let mut list = vec!["tag", "--list"];
+        list.extend(filter.include.iter().map(String::as_str));
+        for tag in run(&repo, &list)?.lines() {
+            if !filter.matches(tag) {
+                args.push(format!("--exclude={}", glob::Pattern::escape(tag)));
+            }
+        }
+    }
+    run(repo, &args.iter().map(String::as_str).collect::<Vec<_>>())
+}
+
+pub fn tags<P: AsRef<Path>>(repo: P, filter: &TagFilter) -> Result<Vec<String>> {
+    Ok(run(repo, &["tag", "--list"])?
+        .lines()
+        .filter(|tag| filter.matches(tag))
+        .map(String::from)
+        .collect())
+}
+
+/// Tags of the `origin` remote that pass the filter
+pub fn remote_tags<P: AsRef<Path>>(repo: P, filter: &TagFilter) -> Result<Vec<String>> {
+    Ok(run(repo, &["ls-remote", "--tags", "--refs", "origin"])?
+        .lines()
+        .filter_map(|line| line.split_once("\trefs/tags/"))
+        .map(|(_, tag)| tag)
+        .filter(|tag| filter.matches(tag))
+        .map(String::from)
+        .collect())
+}
+
+/// Branches and tags of the remote, with the commits they point to
+pub fn remote_refs<P: AsRef<Path>>(repo: P) -> Result<Vec<(String, String)>> {
+    let output = run(repo, &["ls-remote", "--heads", "--tags", "origin"])?;
+    let mut refs: Vec<(String, String)> = vec![];
+    for (id, name) in output.lines().filter_map(|line| line.split_once('\t')) {
+        // Annotated tags are followed by the commit they point to
+        if let Some(name) = name.strip_suffix("^{}") {
+            if let Some(last) = refs.last_mut()
+                && last.0 == name
+            {
+                last.1 = id.to_string();
+            }
+        } else {
+            refs.push((name.to_string(), id.to_string()));
+        }
+    }
+    Ok(refs)
+}
+
+/// Which of the given objects are present in the repository
+pub fn has_objects<P: AsRef<Path>>(repo: P, ids: &[&str]) -> Result<Vec<bool>> {
+    let mut child = Command::new("git")
+        .current_dir(repo.as_ref())
+        .args(["cat-file", "--batch-check"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let input = ids.iter().map(|id| format!("{id}\n")).collect::<String>();
+    let mut stdin = child.stdin.take().expect("piped stdin");
+    // Written in another thread, so that a full stdout doesn't block us
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let result = child.wait_with_output()?;
+    writer.join().expect("stdin writer")?;
+    ensure!(
+        result.status.success(),
+        "error running git cat-file: {:?}; in {:?}",
+        result,
+        repo.as_ref().display(),
+    );
+    let output = String::from_utf8(result.stdout)?;
+    Ok(output.lines().map(|l| !l.ends_with(" missing")).collect())
+}
+
+pub fn head_branch<P: AsRef<Path>>(repo: P) -> Result<String> {
+    run(repo, &["symbolic-ref", "--short", "HEAD"])
+}
+
+pub fn head_branches<P: AsRef<Path>>(repo: P) -> Result<Vec<String>> {
+    let refs = run(
+        repo,
+        &[
+            "for-each-ref",
+            "--points-at=HEAD",
+            "--format=%(refname)",
+            "refs/heads",
+            "refs/remotes",
+        ],
+    )?;
+    Ok(refs
+        .lines()
+        .filter_map(super::branch_name)
+        .map(String::from)
+        .collect())
+}
+
+pub fn is_shallow<P: AsRef<Path>>(repo: P) -> Result<bool> {
+    Ok(run(repo, &["rev-parse", "--is-shallow-repository"])? == "true")
+}
+
+pub fn ref_commit<P: AsRef<Path>>(repo: P, reference: &str) -> Result<String> {
+    run(repo, &["rev-parse", "--short", reference])
+}
+
+pub fn head_commit<P: AsRef<Path>>(repo: P) -> Result<String> {
+    ref_commit(repo, "HEAD")
+}
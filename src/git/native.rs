@@ -0,0 +1,167 @@
+// Copyright (C) 2026 Leandro Lisboa Penz <lpenz@lpenz.org>
+// This file is subject to the terms and conditions defined in
+// file 'LICENSE', which is part of this source code package.
+
+//! In-process backend, using gitoxide
+
+use std::borrow::Cow;
+use std::path::Path;
+
+use color_eyre::Result;
+use color_eyre::eyre::OptionExt;
+
+use gix::ObjectId;
+use gix::bstr::BStr;
+use gix::bstr::BString;
+use gix::hashtable::HashMap;
+use gix::revision::plumbing as revision;
+
+use super::TagFilter;
+
+fn open<P: AsRef<Path>>(repo: P) -> Result<gix::Repository> {
+    Ok(gix::discover(repo.as_ref())?)
+}
+
+/// Tag considered by `describe`
+struct Candidate {
+    name: String,
+    annotated: bool,
+    /// Seconds since the epoch of the tagger, 0 if unknown
+    date: i64,
+}
+
+impl Candidate {
+    /// Whether this tag replaces `other`, that points to the same
+    /// commit and comes first in the order of the names: annotated
+    /// tags take precedence, and then the newest one, as in git.
+    fn replaces(&self, other: &Candidate) -> bool {
+        if self.annotated != other.annotated {
+            return self.annotated;
+        }
+        self.annotated && (other.date == 0 || self.date > other.date)
+    }
+}
+
+/// The commits pointed to by the tags that pass the filter, with the
+/// name of the tag that git would use.
+fn tag_names(
+    repo: &gix::Repository,
+    filter: &TagFilter,
+) -> Result<HashMap<ObjectId, Cow<'static, BStr>>> {
+    let mut tags = vec![];
+    for reference in repo.references()?.tags()? {
+        let Ok(mut reference) = reference else {
+            continue;
+        };
+        let name = reference.name().shorten().to_string();
+        if !filter.matches(&name) {
+            continue;
+        }
+        let target = reference.target().try_id().map(ToOwned::to_owned);
+        let Ok(peeled) = reference.peel_to_id() else {
+            continue;
+        };
+        let peeled = peeled.detach();
+        let annotated = target.is_some_and(|t| t != peeled);
+        let date = target
+            .filter(|_| annotated)
+            .and_then(|t| repo.find_object(t).ok()?.try_into_tag().ok())
+            .and_then(|tag| Some(tag.tagger().ok()??.seconds()))
+            .unwrap_or(0);
+        tags.push((
+            peeled,
+            Candidate {
+                name,
+                annotated,
+                date,
+            },
+        ));
+    }
+    tags.sort_by(|(_, a), (_, b)| a.name.cmp(&b.name));
+    let mut names = HashMap::<ObjectId, Candidate>::default();
+    for (id, candidate) in tags {
+        if names.get(&id).is_none_or(|other| candidate.replaces(other)) {
+            names.insert(id, candidate);
+        }
+    }
+    Ok(names
+        .into_iter()
+        .map(|(id, c)| (id, Cow::Owned(BString::from(c.name))))
+        .collect())
+}
+
+pub fn describe<P: AsRef<Path>>(repo: P, filter: &TagFilter) -> Result<String> {
+    let repo = open(repo)?;
+    let head = repo.head_commit()?;
+    let cache = repo.commit_graph_if_enabled()?;
+    let mut graph = repo.revision_graph(cache.as_ref());
+    let outcome = revision::describe(
+        &head.id,
+        &mut graph,
+        revision::describe::Options {
+            name_by_oid: tag_names(&repo, filter)?,
+            ..revision::describe::Options::default()
+        },
+    )?
+    .ok_or_eyre("no names found, cannot describe anything")?;
+    // Use the same abbreviation as rev-parse --short
+    let hex_len = head.id().shorten()?.hex_len();
+    Ok(outcome.into_format(hex_len).to_string())
+}
+
+pub fn tags<P: AsRef<Path>>(repo: P, filter: &TagFilter) -> Result<Vec<String>> {
+    let repo = open(repo)?;
+    let mut tags = vec![];
+    for reference in repo.references()?.tags()? {
+        let Ok(reference) = reference else {
+            continue;
+        };
+        let name = reference.name().shorten().to_string();
+        if filter.matches(&name) {
+            tags.push(name);
+        }
+    }
+    Ok(tags)
+}
+
+pub fn head_branch<P: AsRef<Path>>(repo: P) -> Result<String> {
+    let repo = open(repo)?;
+    let name = repo.head_name()?.ok_or_eyre("HEAD is detached")?;
+    Ok(name.shorten().to_string())
+}
+
+pub fn head_branches<P: AsRef<Path>>(repo: P) -> Result<Vec<String>> {
+    let repo = open(repo)?;
+    let head = repo.head_id()?.detach();
+    let mut branches = vec![];
+    let references = repo.references()?;
+    for reference in references
+        .local_branches()?
+        .chain(references.remote_branches()?)
+    {
+        let Ok(mut reference) = reference else {
+            continue;
+        };
+        let refname = reference.name().as_bstr().to_string();
+        if reference.peel_to_id().is_ok_and(|id| id.detach() == head)
+            && let Some(name) = super::branch_name(&refname)
+        {
+            branches.push(name.to_string());
+        }
+    }
+    Ok(branches)
+}
+
+pub fn is_shallow<P: AsRef<Path>>(repo: P) -> Result<bool> {
+    Ok(open(repo)?.is_shallow())
+}
+
+pub fn ref_commit<P: AsRef<Path>>(repo: P, reference: &str) -> Result<String> {
+    let repo = open(repo)?;
+    let id = repo.rev_parse_single(reference)?;
+    Ok(id.shorten()?.to_string())
+}
+
+pub fn head_commit<P: AsRef<Path>>(repo: P) -> Result<String> {
+    ref_commit(repo, "HEAD")
+}
@@ -2,24 +2,104 @@
 // This file is subject to the terms and conditions defined in
 // file 'LICENSE', which is part of this source code package.
 
+pub mod ci;
+pub mod config;
+pub mod event;
 pub mod git;
+pub mod node;
 pub mod python;
 pub mod rust;
+pub mod source;
+pub mod version;
 
+use std::collections::hash_map::RandomState;
 use std::env;
 use std::error::Error;
+use std::fmt;
 use std::fs;
+use std::hash::BuildHasher;
+use std::hash::Hasher;
 use std::io::Write;
+use std::iter;
 use std::path::Path;
+use std::path::PathBuf;
+use std::rc::Rc;
 use std::str;
 
 use clap::Parser;
+use clap::ValueEnum;
 
 use color_eyre::Result;
 use color_eyre::eyre::bail;
 
 use regex::Regex;
 
+use serde::Serialize;
+use serde::Serializer;
+use serde::ser::SerializeMap;
+
+use source::Position;
+use source::VersionSource;
+
+/// Escapes the message of a workflow command
+fn escape_data(s: &str) -> String {
+    s.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escapes a property of a workflow command
+fn escape_property(s: &str) -> String {
+    escape_data(s).replace(':', "%3A").replace(',', "%2C")
+}
+
+/// Escapes a value for a markdown table cell or list item
+fn markdown_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('|', "\\|")
+        .replace('\n', "<br>")
+}
+
+/// Problem that is reported as a github workflow command, which shows
+/// up in the summary of the run and inline in the diff of the file.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct Annotation {
+    pub file: Option<PathBuf>,
+    pub position: Option<Position>,
+    pub title: String,
+    pub message: String,
+}
+
+impl Annotation {
+    /// The workflow command with the given level: `error`, `warning`
+    /// or `notice`.
+    pub fn command(&self, level: &str) -> String {
+        let mut properties = vec![];
+        if let Some(ref file) = self.file {
+            let file = escape_property(&file.display().to_string());
+            properties.push(format!("file={file}"));
+        }
+        if let Some(position) = self.position {
+            properties.push(format!("line={}", position.line));
+            properties.push(format!("col={}", position.col));
+        }
+        properties.push(format!("title={}", escape_property(&self.title)));
+        format!(
+            "::{level} {}::{}",
+            properties.join(","),
+            escape_data(&self.message)
+        )
+    }
+}
+
+impl fmt::Display for Annotation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.title, self.message)
+    }
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct Info {
     pub pwd_basename: String,
@@ -28,9 +108,51 @@ pub struct Info {
     pub is_main: Option<bool>,
     pub is_push_tag: Option<bool>,
     pub is_push_main: Option<bool>,
+    /// Names of the trunk branches [default: main, master]
+    pub main_branches: Vec<String>,
+    /// Default branch of the repository, the trunk if `main_branches`
+    /// is not given
+    pub default_branch: Option<String>,
+    /// Globs of the maintenance branches, e.g. `release/*`
+    pub release_branches: Vec<String>,
+    pub is_release_branch: Option<bool>,
+    pub is_push_release_branch: Option<bool>,
+    /// HEAD is at the tip of a release branch
+    pub is_release_branch_here: Option<bool>,
+    pub is_pull_request: Option<bool>,
+    pub is_release: Option<bool>,
+    /// Flags of the release, from the event payload
+    pub is_prerelease: Option<bool>,
+    pub is_draft: Option<bool>,
+    /// A tag was pushed or a release of a tag was published
+    pub is_tag_release: Option<bool>,
+    pub is_workflow_dispatch: Option<bool>,
+    /// Name of the `workflow_dispatch` input with the version
+    pub dispatch_input: Option<String>,
+    /// Version given in the `workflow_dispatch` input, `v` stripped
+    pub dispatch_version: Option<String>,
+    /// Flags of the push, from the event payload
+    pub is_created: Option<bool>,
+    pub is_deleted: Option<bool>,
+    pub is_forced: Option<bool>,
+    /// Comma-separated labels of the pull request
+    pub pr_labels: Option<String>,
+    /// File with the payload of the event
+    pub event_path: Option<PathBuf>,
+    /// Running outside of CI: the event is inferred from the
+    /// repository and the network is not used.
+    pub local: bool,
+    pub event: event::Event,
+    /// Number of the pull request, from `refs/pull/<number>/merge`
+    pub pr_number: Option<String>,
+    pub pr_head_ref: Option<String>,
+    pub pr_base_ref: Option<String>,
     pub commit: String,
     pub commit_main: Option<String>,
     pub is_main_here: Option<bool>,
+    pub tag_status: Option<git::TagStatus>,
+    /// Problems that do not prevent the outputs from being generated
+    pub warnings: Vec<Annotation>,
     pub git_describe_tags: String,
     pub tag_latest: String,
     pub distance: Option<String>,
@@ -40,24 +162,72 @@ pub struct Info {
     pub tag_latest_ltrimv: Option<String>,
     pub tag_distance_ltrimv: Option<String>,
     pub tag_head_ltrimv: Option<String>,
-    pub rust_crate_name: Option<String>,
-    pub rust_crate_version: Option<String>,
-    pub python_module_name: Option<String>,
-    pub python_module_version: Option<String>,
+    /// `tag_latest_ltrimv` parsed
+    pub tag_version: Option<version::Version>,
+    pub version_major: Option<String>,
+    pub version_minor: Option<String>,
+    pub version_patch: Option<String>,
+    pub version_prerelease: Option<String>,
+    pub version_build: Option<String>,
+    pub version_is_semver: Option<bool>,
+    pub sources: Vec<Rc<dyn VersionSource>>,
+    pub sources_outputs: Vec<(String, String)>,
+    pub version_mismatches: Vec<Annotation>,
     pub version_mismatch: Option<String>,
+    pub version_mismatch_count: String,
     pub version_tagged: Option<String>,
     pub version_commit: Option<String>,
     pub version_docker_ci: String,
+    pub version_pr: Option<String>,
+    /// All the tags in the repository that pass the filter
+    pub tags: Vec<String>,
+    pub docker_tags: Option<String>,
+    pub docker_tags_lines: Option<String>,
     pub override_version_tagged: Option<String>,
     pub override_version_commit: Option<String>,
     pub override_version_docker_ci: Option<String>,
+    pub tag_filter: git::TagFilter,
+    /// Component of a monorepo, whose tags are `<component>/<version>`
+    pub component: Option<String>,
+    /// Directory of the manifests, relative to the repository
+    pub manifest_dir: PathBuf,
     pub name: String,
     pub rpm_basename: String,
     pub deb_basename: String,
 }
 
 impl Info {
+    /// Settings from the configuration file, that the environment
+    /// variables override.
+    pub fn apply_config(&mut self, config: &config::Config) {
+        if let Some(ref include) = config.tags.include {
+            self.tag_filter.include = include.clone();
+        }
+        if let Some(ref exclude) = config.tags.exclude {
+            self.tag_filter.exclude = exclude.clone();
+        }
+        if let Some(versions_only) = config.tags.versions_only {
+            self.tag_filter.versions_only = versions_only;
+        }
+        if let Some(ref component) = config.tags.component {
+            self.component = Some(component.clone());
+        }
+        if let Some(ref main) = config.branches.main {
+            self.main_branches = main.clone();
+        }
+        if let Some(ref release) = config.branches.release {
+            self.release_branches = release.clone();
+        }
+        if let Some(ref dispatch_input) = config.branches.dispatch_input {
+            self.dispatch_input = Some(dispatch_input.clone());
+        }
+        if let Some(ref dir) = config.manifests.dir {
+            self.manifest_dir = dir.clone();
+        }
+    }
+
     pub fn parse_env(&mut self, enviter: impl Iterator<Item = (String, String)>) {
+        let mut git_ref = None;
         for (k, v) in enviter {
             match k.as_str() {
                 "PWD" => {
@@ -70,10 +240,36 @@ impl Info {
                 }
                 "GITHUB_EVENT_NAME" => {
                     self.is_push = Some(v == "push");
+                    self.is_pull_request = Some(v == "pull_request" || v == "pull_request_target");
+                    self.is_release = Some(v == "release");
+                    self.is_workflow_dispatch = Some(v == "workflow_dispatch");
+                }
+                "VERSION_GEN_MAIN_BRANCHES" => {
+                    self.main_branches = env_list(&v);
+                }
+                "VERSION_GEN_LOCAL_MODE" => {
+                    self.local = env_bool(&v);
+                }
+                "VERSION_GEN_DEFAULT_BRANCH" => {
+                    self.default_branch = Some(v);
+                }
+                "VERSION_GEN_RELEASE_BRANCHES" => {
+                    self.release_branches = env_list(&v);
+                }
+                "VERSION_GEN_DISPATCH_INPUT" => {
+                    self.dispatch_input = Some(v);
+                }
+                "GITHUB_EVENT_PATH" if !v.is_empty() => {
+                    self.event_path = Some(v.into());
                 }
                 "GITHUB_REF" => {
-                    self.is_tag = Some(v.starts_with("refs/tags/"));
-                    self.is_main = Some(v == "refs/heads/main" || v == "refs/heads/master");
+                    git_ref = Some(v);
+                }
+                "GITHUB_HEAD_REF" if !v.is_empty() => {
+                    self.pr_head_ref = Some(v);
+                }
+                "GITHUB_BASE_REF" if !v.is_empty() => {
+                    self.pr_base_ref = Some(v);
                 }
                 "OVERRIDE_VERSION_TAGGED" => {
                     self.override_version_tagged = Some(v);
@@ -84,23 +280,112 @@ impl Info {
                 "OVERRIDE_VERSION_DOCKER_CI" => {
                     self.override_version_docker_ci = Some(v);
                 }
+                "VERSION_GEN_TAG_INCLUDE" => {
+                    self.tag_filter.include = env_list(&v);
+                }
+                "VERSION_GEN_TAG_EXCLUDE" => {
+                    self.tag_filter.exclude = env_list(&v);
+                }
+                "VERSION_GEN_TAG_VERSIONS_ONLY" => {
+                    self.tag_filter.versions_only = env_bool(&v);
+                }
+                "VERSION_GEN_COMPONENT" => {
+                    self.component = Some(v);
+                }
+                "VERSION_GEN_COMPONENT_DIR" => {
+                    self.manifest_dir = v.into();
+                }
                 _ => {}
             }
         }
+        // The component is only known after all variables are read
+        if let Some(ref component) = self.component {
+            self.tag_filter.prefix = format!("{component}/");
+            if self.manifest_dir.as_os_str().is_empty() {
+                self.manifest_dir = component.into();
+            }
+        }
+        if self.main_branches.is_empty() {
+            self.main_branches = match self.default_branch {
+                Some(ref branch) => vec![branch.clone()],
+                None => vec!["main".into(), "master".into()],
+            };
+        }
+        if let Some(git_ref) = git_ref {
+            self.parse_ref(&git_ref);
+        }
     }
 
-    pub fn parse_files<P: AsRef<Path>>(&mut self, repo: P) -> Result<()> {
-        if let Some(cratedata) = rust::crate_data(&repo)? {
-            self.rust_crate_name = Some(cratedata.name);
-            self.rust_crate_version = Some(cratedata.version);
+    /// Classifies the ref that triggered the event
+    fn parse_ref(&mut self, git_ref: &str) {
+        let tags = format!("refs/tags/{}", self.tag_filter.prefix);
+        self.is_tag = Some(git_ref.starts_with(&tags));
+        let branch = git_ref.strip_prefix("refs/heads/");
+        self.is_main = Some(branch.is_some_and(|b| self.main_branches.iter().any(|m| m == b)));
+        self.is_release_branch = Some(branch.is_some_and(|b| self.is_release_branch_name(b)));
+        self.pr_number = git_ref
+            .strip_prefix("refs/pull/")
+            .and_then(|r| r.split('/').next())
+            .map(String::from);
+    }
+
+    /// Infers the event from the state of the repository, as if HEAD
+    /// was just pushed: a push of the branch that is checked out, or
+    /// of the tag HEAD is detached at. Used in local mode, for the
+    /// parts that were not given explicitly.
+    pub fn infer_event<P: AsRef<Path>>(&mut self, repo: P) {
+        if self.is_tag.is_none() {
+            let git_ref = match (git::head_branch(&repo), &self.tag_head) {
+                (Ok(branch), _) => Some(format!("refs/heads/{branch}")),
+                (Err(_), Some(tag)) => Some(format!("refs/tags/{tag}")),
+                (Err(_), None) => None,
+            };
+            if let Some(git_ref) = git_ref {
+                self.parse_ref(&git_ref);
+            }
+        }
+        if self.is_push.is_none() {
+            self.is_push = Some(true);
+            self.is_pull_request = Some(false);
+            self.is_release = Some(false);
+            self.is_workflow_dispatch = Some(false);
+        }
+    }
+
+    fn is_release_branch_name(&self, branch: &str) -> bool {
+        self.release_branches
+            .iter()
+            .any(|p| git::glob_matches(p, branch))
+    }
+
+    pub fn parse_event<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        self.event = event::Event::read(path)?;
+        self.is_prerelease = self.event.release_prerelease;
+        self.is_draft = self.event.release_draft;
+        self.is_created = self.event.created;
+        self.is_deleted = self.event.deleted;
+        self.is_forced = self.event.forced;
+        if self.is_pull_request == Some(true) {
+            self.pr_labels = Some(self.event.labels.join(","));
         }
-        if let Some(data) = python::module_data(&repo)? {
-            self.python_module_name = Some(data.name);
-            self.python_module_version = Some(data.version);
+        let input = self.dispatch_input.as_deref().unwrap_or("version");
+        if self.is_workflow_dispatch == Some(true)
+            && let Some(version) = self.event.input(input)
+            && !version.is_empty()
+        {
+            if !git::looks_like_version(version) {
+                bail!("invalid version {version:?} in workflow_dispatch input {input}");
+            }
+            self.dispatch_version = Some(version.strip_prefix('v').unwrap_or(version).into());
         }
         Ok(())
     }
 
+    pub fn parse_files<P: AsRef<Path>>(&mut self, repo: P) -> Result<()> {
+        self.sources = source::detect_all(repo.as_ref().join(&self.manifest_dir))?;
+        Ok(())
+    }
+
     pub fn parse_describe(&mut self, s0: impl AsRef<str>) -> Result<()> {
         let s = s0.as_ref();
         self.git_describe_tags = s.into();
@@ -118,52 +403,75 @@ impl Info {
 
     pub fn eval(&mut self) -> Result<()> {
         // Evaluate trivial parameters:
-        self.is_push_tag = match (self.is_push, self.is_tag) {
+        // Pushes that delete the ref don't count
+        let is_push = self.is_push.map(|p| p && self.is_deleted != Some(true));
+        self.is_push_tag = match (is_push, self.is_tag) {
             (Some(a), Some(b)) => Some(a && b),
             _ => None,
         };
-        self.is_push_main = match (self.is_push, self.is_main) {
+        self.is_push_main = match (is_push, self.is_main) {
             (Some(a), Some(b)) => Some(a && b),
             _ => None,
         };
-        let re = Regex::new(r"^v?(?P<tag_ltrimv>.*)$")?;
+        self.is_push_release_branch = match (is_push, self.is_release_branch) {
+            (Some(a), Some(b)) => Some(a && b),
+            _ => None,
+        };
+        // Only these release activities make the tag public; drafts
+        // don't have a tag yet
+        let is_published = self.is_release == Some(true)
+            && self.is_draft != Some(true)
+            && matches!(
+                self.event.action.as_deref(),
+                Some("published" | "released" | "prereleased")
+            );
+        self.is_tag_release = match (is_push, self.is_tag) {
+            // A version given manually is released as if it was tagged
+            _ if self.dispatch_version.is_some() => Some(true),
+            (Some(a), Some(b)) => Some((a || is_published) && b),
+            _ => None,
+        };
         if let Some(ref distance) = self.distance {
             let dash_distance = format!("-{distance}");
             self.dash_distance = Some(dash_distance.clone());
             let tag_distance = format!("{}{}", self.tag_latest, dash_distance);
             self.tag_distance = Some(tag_distance.clone());
-            self.tag_latest_ltrimv = Some(re.replace(&self.tag_latest, "$tag_ltrimv").into());
-            self.tag_distance_ltrimv = Some(re.replace(&tag_distance, "$tag_ltrimv").into());
+            self.tag_latest_ltrimv = Some(self.ltrimv(&self.tag_latest));
+            self.tag_distance_ltrimv = Some(self.ltrimv(&tag_distance));
         }
         if let Some(ref tag_head) = self.tag_head {
-            self.tag_head_ltrimv = Some(re.replace(tag_head, "$tag_ltrimv").into());
+            self.tag_head_ltrimv = Some(self.ltrimv(tag_head));
         }
+        self.eval_version();
         // Evaluate version outputs, correlating the previous variables
-        self.name = if let Some(name) = &self.rust_crate_name {
-            name.clone()
-        } else if let Some(name) = &self.python_module_name {
-            name.clone()
+        self.name = if let Some(source) = self.sources.first() {
+            source.name().to_string()
+        } else if let Some(ref component) = self.component {
+            component.clone()
         } else {
             self.pwd_basename.clone()
         };
-        if self.is_push_tag == Some(true) {
+        if self.is_tag_release == Some(true) {
             self.version_tagged = self
                 .override_version_tagged
                 .as_ref()
+                .or(self.dispatch_version.as_ref())
                 .or(self.tag_head_ltrimv.as_ref())
                 .cloned();
             self.version_commit = self
                 .override_version_commit
                 .as_ref()
+                .or(self.dispatch_version.as_ref())
                 .or(self.tag_latest_ltrimv.as_ref())
                 .cloned();
             self.version_docker_ci = self
                 .override_version_docker_ci
                 .as_ref()
+                .or(self.dispatch_version.as_ref())
                 .or(self.tag_latest_ltrimv.as_ref())
                 .cloned()
                 .unwrap();
-        } else if self.is_push_main == Some(true) {
+        } else if self.is_push_main == Some(true) || self.is_push_release_branch == Some(true) {
             if let Some(distance_str) = &self.distance
                 && let Ok(distance) = distance_str.parse::<u32>()
                 && distance > 0
@@ -178,10 +486,16 @@ impl Info {
                     .or(self.tag_distance_ltrimv.as_ref())
                     .cloned();
             }
+            // Only the trunk is the docker "latest"
+            let docker_ci = if self.is_push_main == Some(true) {
+                "latest"
+            } else {
+                "null"
+            };
             self.version_docker_ci = self
                 .override_version_docker_ci
                 .as_ref()
-                .unwrap_or(&String::from("latest"))
+                .unwrap_or(&String::from(docker_ci))
                 .clone();
         } else {
             self.version_docker_ci = self
@@ -190,6 +504,17 @@ impl Info {
                 .unwrap_or(&String::from("null"))
                 .clone();
         }
+        self.version_pr = if self.is_pull_request == Some(true)
+            && let Some(ref number) = self.pr_number
+            && let Some(ref tag_latest_ltrimv) = self.tag_latest_ltrimv
+            && let Some(ref distance) = self.distance
+        {
+            Some(format!("{tag_latest_ltrimv}-pr.{number}.{distance}"))
+        } else {
+            None
+        };
+        self.eval_docker_tags();
+        self.eval_sources();
         if let Some(version_commit) = &self.version_commit {
             // If we have a full version_commit, use it.
             // (i.e. we are pushing a tag or main after a tag)
@@ -205,59 +530,392 @@ impl Info {
             self.deb_basename = self.name.clone();
         }
         // Warnings
-        if let Some(tag_latest_ltrimv) = &self.tag_latest_ltrimv {
-            if self.is_push_tag == Some(true) || self.is_push_main == Some(true) {
-                if let Some(ref version) = self.rust_crate_version
-                    && version != tag_latest_ltrimv
-                {
-                    self.version_mismatch = Some(format!(
-                        "file=Cargo.toml::Version mismatch: tag {tag_latest_ltrimv} != {version} from Cargo.toml",
-                    ));
-                }
-                if let Some(ref version) = self.python_module_version
-                    && version != tag_latest_ltrimv
-                {
-                    self.version_mismatch = Some(format!(
-                        "file=setup.cfg::Version mismatch: tag {tag_latest_ltrimv} != {version} from setup.cfg",
-                    ));
+        self.version_mismatches = vec![];
+        let (what, released) = match self.dispatch_version {
+            Some(ref v) => ("input", Some(v)),
+            None => ("tag", self.tag_latest_ltrimv.as_ref()),
+        };
+        if let Some(released) = released {
+            if self.is_tag_release == Some(true)
+                || self.is_push_main == Some(true)
+                || self.is_push_release_branch == Some(true)
+            {
+                for source in self.sources.iter().filter(|s| s.checked()) {
+                    if let Some(version) = source.version()
+                        && version != released
+                    {
+                        let file = self.manifest_dir.join(source.file());
+                        self.version_mismatches.push(Annotation {
+                            message: format!(
+                                "{what} {released} != {version} from {}",
+                                file.display()
+                            ),
+                            file: Some(file),
+                            position: source.position(),
+                            title: "Version mismatch".into(),
+                        });
+                    }
                 }
             }
-            if self.is_push_tag == Some(true) && self.is_main_here != Some(true) {
-                self.version_mismatch = Some(format!(
-                    "file=.git::Version mismatch::Version tag {} pushed over {}, but main branch is at {:?}",
-                    tag_latest_ltrimv, self.commit, self.commit_main
-                ));
+            if self.is_tag_release == Some(true)
+                && self.dispatch_version.is_none()
+                && self.is_main_here != Some(true)
+                && self.is_release_branch_here != Some(true)
+            {
+                self.version_mismatches.push(Annotation {
+                    title: "Version mismatch".into(),
+                    message: format!(
+                        "tag {released} pushed over {}, but main branch is at {}",
+                        self.commit,
+                        self.commit_main.as_deref().unwrap_or("an unknown commit")
+                    ),
+                    ..Annotation::default()
+                });
             }
         }
+        self.version_mismatch = if self.version_mismatches.is_empty() {
+            None
+        } else {
+            let messages = self.version_mismatches.iter().map(|m| m.to_string());
+            Some(messages.collect::<Vec<_>>().join("; "))
+        };
+        self.version_mismatch_count = self.version_mismatches.len().to_string();
         Ok(())
     }
 
+    /// The tag without the component prefix and the optional `v`
+    fn ltrimv(&self, tag: &str) -> String {
+        let tag = tag.strip_prefix(&self.tag_filter.prefix).unwrap_or(tag);
+        tag.strip_prefix('v').unwrap_or(tag).to_string()
+    }
+
+    /// All the docker tags: `version_docker_ci` and, when a stable
+    /// release is pushed, the floating `major.minor` and `major` tags,
+    /// plus `latest` if it's the highest release.
+    fn eval_docker_tags(&mut self) {
+        let mut tags = vec![];
+        if self.version_docker_ci != "null" {
+            tags.push(self.version_docker_ci.clone());
+        }
+        if self.is_tag_release == Some(true)
+            && self.override_version_docker_ci.is_none()
+            && let Some(ref v) = self.tag_version
+            && v.is_stable()
+        {
+            let stable = self
+                .tags
+                .iter()
+                .filter_map(|t| version::Version::parse(&self.ltrimv(t)))
+                .filter(|t| t.is_stable())
+                .map(|t| t.numbers())
+                .collect::<Vec<_>>();
+            // The floating tags only move to the highest release they
+            // cover, so that maintenance releases don't take them back
+            let numbers = v.numbers();
+            let is_highest = |covers: &dyn Fn(&(u64, u64, u64)) -> bool| {
+                stable.iter().filter(|n| covers(n)).all(|n| numbers >= *n)
+            };
+            if let Some(minor) = v.minor
+                && is_highest(&|n| n.0 == v.major && n.1 == minor)
+            {
+                tags.push(format!("{}.{}", v.major, minor));
+            }
+            if is_highest(&|n| n.0 == v.major) {
+                tags.push(v.major.to_string());
+            }
+            if is_highest(&|_| true) {
+                tags.push("latest".to_string());
+            }
+        }
+        tags.dedup();
+        if tags.is_empty() {
+            self.docker_tags = None;
+            self.docker_tags_lines = None;
+        } else {
+            self.docker_tags = Some(tags.join(","));
+            self.docker_tags_lines = Some(tags.join("\n"));
+        }
+    }
+
+    /// Components of the version being released, or of the latest tag
+    fn eval_version(&mut self) {
+        self.tag_version = self
+            .dispatch_version
+            .as_deref()
+            .or(self.tag_latest_ltrimv.as_deref())
+            .and_then(version::Version::parse);
+        let v = self.tag_version.as_ref();
+        self.version_major = v.map(|v| v.major.to_string());
+        self.version_minor = v.and_then(|v| v.minor).map(|n| n.to_string());
+        self.version_patch = v.and_then(|v| v.patch).map(|n| n.to_string());
+        self.version_prerelease = v.and_then(|v| v.prerelease.clone());
+        self.version_build = v.and_then(|v| v.build.clone());
+        self.version_is_semver = self
+            .dispatch_version
+            .as_ref()
+            .or(self.tag_latest_ltrimv.as_ref())
+            .map(|_| v.is_some_and(|v| v.is_semver));
+    }
+
+    /// Primary source of the given kind, identified by its output
+    /// prefix.
+    pub fn source(&self, prefix: &str) -> Option<&dyn VersionSource> {
+        self.sources
+            .iter()
+            .find(|s| s.prefix() == prefix)
+            .map(|s| s.as_ref())
+    }
+
+    /// Outputs of the sources: name and version of the primary source
+    /// of each kind and, when there's more than one of that kind, all
+    /// the names and the version of each one.
+    fn eval_sources(&mut self) {
+        self.sources_outputs = vec![];
+        let mut prefixes = self.sources.iter().map(|s| s.prefix()).collect::<Vec<_>>();
+        prefixes.dedup();
+        for prefix in prefixes {
+            let group = self
+                .sources
+                .iter()
+                .filter(|s| s.prefix() == prefix)
+                .collect::<Vec<_>>();
+            let primary = group[0];
+            let outputs = &mut self.sources_outputs;
+            outputs.push((format!("{prefix}_name"), primary.name().to_string()));
+            if let Some(version) = primary.version() {
+                outputs.push((format!("{prefix}_version"), version.to_string()));
+            }
+            if group.len() > 1 {
+                let names = group.iter().map(|s| s.name()).collect::<Vec<_>>();
+                outputs.push((format!("{prefix}_names"), names.join(" ")));
+                for source in group {
+                    if let Some(version) = source.version() {
+                        let key = format!("{prefix}_version_{}", source.name());
+                        outputs.push((key, version.to_string()));
+                    }
+                }
+            }
+        }
+    }
+
     pub fn from_workspace<P: AsRef<Path>>(
         repo: P,
+        config: &config::Config,
         enviter: impl Iterator<Item = (String, String)>,
     ) -> Result<Info> {
-        let _ = git::unshallow(&repo);
-        let commit = git::head_commit(&repo)?;
-        let commit_main = git::ref_commit(&repo, "refs/remotes/origin/main")
-            .or_else(|_| git::ref_commit(&repo, "refs/remotes/origin/master"))
-            .or_else(|_| git::ref_commit(&repo, "refs/heads/main"))
-            .or_else(|_| git::ref_commit(&repo, "refs/heads/master"))
-            .ok();
-        let is_main_here = commit_main.as_ref().map(|c| c == &commit);
-        let mut info = Info {
-            commit,
-            commit_main,
-            is_main_here,
-            ..Info::default()
-        };
+        let mut info = Info::default();
+        info.apply_config(config);
         info.parse_env(enviter);
+        let head_commit = git::head_commit(&repo);
+        if !info.local
+            && head_commit.is_ok()
+            && let Err(e) = git::fetch_history(
+                &repo,
+                &info.tag_filter,
+                &info.main_branches,
+                &info.release_branches,
+            )
+        {
+            info.warnings.push(Annotation {
+                title: "Could not fetch the git history".into(),
+                message: e.to_string(),
+                ..Annotation::default()
+            });
+        }
+        info.commit = head_commit.unwrap_or_default();
+        let main_refs = ["refs/remotes/origin", "refs/heads"]
+            .iter()
+            .flat_map(|prefix| {
+                info.main_branches
+                    .iter()
+                    .map(move |branch| format!("{prefix}/{branch}"))
+            });
+        info.commit_main = main_refs
+            .into_iter()
+            .find_map(|reference| git::ref_commit(&repo, &reference).ok());
+        info.is_main_here = info.commit_main.as_ref().map(|c| c == &info.commit);
+        if !info.release_branches.is_empty() {
+            let branches = git::head_branches(&repo).unwrap_or_default();
+            info.is_release_branch_here =
+                Some(branches.iter().any(|b| info.is_release_branch_name(b)));
+        }
+        if let Some(event_path) = info.event_path.clone() {
+            info.parse_event(event_path)?;
+        }
         info.parse_files(&repo)?;
-        if let Ok(gitdescr) = git::describe(&repo) {
+        info.tags = git::tags(&repo, &info.tag_filter).unwrap_or_default();
+        if let Ok(gitdescr) = git::describe(&repo, &info.tag_filter) {
             info.parse_describe(gitdescr)?;
+            info.tag_status = Some(git::TagStatus::Found);
+        } else {
+            let status = git::tag_status(&repo, &info.tag_filter, !info.local);
+            info.tag_status = Some(status);
+            info.warnings.extend(tag_status_warning(status));
+        }
+        if info.local {
+            info.infer_event(&repo);
         }
         info.eval()?;
         Ok(info)
     }
+
+    /// Kind of event and of ref, from the flags
+    fn event_kind(&self) -> (&'static str, &'static str) {
+        let event = [
+            (self.is_push, "push"),
+            (self.is_pull_request, "pull request"),
+            (self.is_release, "release"),
+            (self.is_workflow_dispatch, "workflow dispatch"),
+        ]
+        .into_iter()
+        .find(|(flag, _)| *flag == Some(true))
+        .map_or(
+            if self.is_push.is_some() {
+                "other"
+            } else {
+                "unknown"
+            },
+            |(_, name)| name,
+        );
+        let git_ref = [
+            (self.is_tag, "tag"),
+            (self.is_main, "trunk branch"),
+            (self.is_release_branch, "release branch"),
+            (self.pr_number.as_ref().map(|_| true), "pull request"),
+        ]
+        .into_iter()
+        .find(|(flag, _)| *flag == Some(true))
+        .map_or(
+            if self.is_tag.is_some() {
+                "other"
+            } else {
+                "unknown"
+            },
+            |(_, name)| name,
+        );
+        (event, git_ref)
+    }
+
+    /// Markdown summary of the run: the event classification, why the
+    /// version was or wasn't produced, all the outputs and the problems
+    /// found.
+    pub fn step_summary(&self) -> String {
+        let (event, git_ref) = self.event_kind();
+        let tag_release = self.is_tag_release.map_or("unknown", bool2str);
+        let tag_status = self.tag_status.map_or("unknown", |s| s.as_str());
+        let mut summary = vec![
+            "### Version".to_string(),
+            String::new(),
+            "| Event | Ref | Tag release | Tag status |".to_string(),
+            "| --- | --- | --- | --- |".to_string(),
+            format!("| {event} | {git_ref} | {tag_release} | {tag_status} |"),
+            String::new(),
+        ];
+        let mut explanation = if self.is_tag_release == Some(true) {
+            "A tag is being released: `version_tagged` and `version_commit` are defined."
+        } else if self.is_push_main == Some(true) {
+            "The trunk branch was pushed: `version_tagged` is not defined."
+        } else if self.is_push_release_branch == Some(true) {
+            "A release branch was pushed: `version_tagged` is not defined."
+        } else {
+            "Not a release nor a push of a trunk or release branch: \
+             `version_tagged` and `version_commit` are not defined."
+        }
+        .to_string();
+        // Branch pushes only get a version_commit after a tag
+        if self.is_tag_release != Some(true)
+            && (self.is_push_main == Some(true) || self.is_push_release_branch == Some(true))
+        {
+            let tag = markdown_escape(&self.tag_latest);
+            explanation.push(' ');
+            explanation.push_str(&match (&self.distance, &self.version_commit) {
+                (None, _) => {
+                    "No tag was found, so `version_commit` is not defined either.".to_string()
+                }
+                (Some(distance), Some(version)) => format!(
+                    "The most recent tag is {tag}, {distance} commit(s) behind, so \
+                     `version_commit` is {}.",
+                    markdown_escape(version)
+                ),
+                (Some(_), None) => format!(
+                    "The commit is already tagged with {tag}, so `version_commit` is not \
+                     defined: it was defined when the tag was pushed."
+                ),
+            });
+        }
+        summary.push(explanation);
+        summary.push(String::new());
+        summary.push("| Output | Value |".to_string());
+        summary.push("| --- | --- |".to_string());
+        for (k, v) in self {
+            summary.push(format!("| `{k}` | {} |", markdown_escape(v)));
+        }
+        for (title, annotations) in [
+            ("Version mismatches", &self.version_mismatches),
+            ("Warnings", &self.warnings),
+        ] {
+            if annotations.is_empty() {
+                continue;
+            }
+            summary.push(String::new());
+            summary.push(format!("#### {title}"));
+            summary.push(String::new());
+            for annotation in annotations {
+                let location = match (&annotation.file, annotation.position) {
+                    (Some(file), Some(position)) => {
+                        format!("{}:{}: ", file.display(), position.line)
+                    }
+                    (Some(file), None) => format!("{}: ", file.display()),
+                    _ => String::new(),
+                };
+                let line = format!("{location}{annotation}");
+                summary.push(format!("- {}", markdown_escape(&line)));
+            }
+        }
+        summary.push(String::new());
+        summary.join("\n")
+    }
+}
+
+/// Warning with the fix for a checkout where no tag was found
+fn tag_status_warning(status: git::TagStatus) -> Option<Annotation> {
+    let (title, message) = match status {
+        git::TagStatus::Found | git::TagStatus::NoTags => return None,
+        git::TagStatus::Shallow => (
+            "No tag found in the shallow clone",
+            "the tag may be in the history that was not fetched; \
+             use fetch-depth: 0 in actions/checkout",
+        ),
+        git::TagStatus::NotFetched => (
+            "Tags not fetched",
+            "the remote has tags, but they were not fetched; \
+             use fetch-depth: 0 in actions/checkout",
+        ),
+        git::TagStatus::NoGit => (
+            "Git not available",
+            "the repository could not be read; actions/checkout only \
+             clones it when git is installed, otherwise it downloads just \
+             the files",
+        ),
+    };
+    Some(Annotation {
+        title: title.into(),
+        message: message.into(),
+        ..Annotation::default()
+    })
+}
+
+/// Splits a list given in an environment variable, separated by
+/// commas or spaces.
+pub fn env_list(v: &str) -> Vec<String> {
+    v.split([',', ' ', '\n'])
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+pub fn env_bool(v: &str) -> bool {
+    matches!(v.trim(), "true" | "1" | "yes")
 }
 
 pub fn bool2str(b: bool) -> &'static str {
@@ -265,8 +923,8 @@ pub fn bool2str(b: bool) -> &'static str {
 }
 
 impl<'a> IntoIterator for &'a Info {
-    type Item = (&'static str, &'a str);
-    type IntoIter = std::vec::IntoIter<(&'static str, &'a str)>;
+    type Item = (String, &'a str);
+    type IntoIter = std::vec::IntoIter<(String, &'a str)>;
     fn into_iter(self) -> Self::IntoIter {
         let mut vec: Vec<(&'static str, &'a str)> = vec![
             ("name", &self.name),
@@ -277,6 +935,7 @@ impl<'a> IntoIterator for &'a Info {
             ("version_docker_ci", &self.version_docker_ci),
             ("rpm_basename", &self.rpm_basename),
             ("deb_basename", &self.deb_basename),
+            ("version_mismatch_count", &self.version_mismatch_count),
         ];
         if let Some(ref v) = self.is_push {
             vec.push(("is_push", bool2str(*v)));
@@ -293,6 +952,54 @@ impl<'a> IntoIterator for &'a Info {
         if let Some(ref v) = self.is_push_main {
             vec.push(("is_push_main", bool2str(*v)));
         }
+        if let Some(ref v) = self.is_release {
+            vec.push(("is_release", bool2str(*v)));
+        }
+        if let Some(ref v) = self.is_prerelease {
+            vec.push(("is_prerelease", bool2str(*v)));
+        }
+        if let Some(ref v) = self.is_draft {
+            vec.push(("is_draft", bool2str(*v)));
+        }
+        if let Some(ref v) = self.is_tag_release {
+            vec.push(("is_tag_release", bool2str(*v)));
+        }
+        if let Some(ref v) = self.is_workflow_dispatch {
+            vec.push(("is_workflow_dispatch", bool2str(*v)));
+        }
+        if let Some(ref v) = self.is_created {
+            vec.push(("is_created", bool2str(*v)));
+        }
+        if let Some(ref v) = self.is_deleted {
+            vec.push(("is_deleted", bool2str(*v)));
+        }
+        if let Some(ref v) = self.is_forced {
+            vec.push(("is_forced", bool2str(*v)));
+        }
+        if let Some(ref t) = self.pr_labels {
+            vec.push(("pr_labels", t));
+        }
+        if let Some(ref v) = self.tag_status {
+            vec.push(("tag_status", v.as_str()));
+        }
+        if let Some(ref v) = self.is_pull_request {
+            vec.push(("is_pull_request", bool2str(*v)));
+        }
+        if let Some(ref t) = self.pr_number {
+            vec.push(("pr_number", t));
+        }
+        if let Some(ref t) = self.pr_head_ref {
+            vec.push(("pr_head_ref", t));
+        }
+        if let Some(ref t) = self.pr_base_ref {
+            vec.push(("pr_base_ref", t));
+        }
+        if let Some(ref v) = self.is_release_branch {
+            vec.push(("is_release_branch", bool2str(*v)));
+        }
+        if let Some(ref v) = self.is_push_release_branch {
+            vec.push(("is_push_release_branch", bool2str(*v)));
+        }
         if let Some(ref v) = self.commit_main {
             vec.push(("commit_main", v));
         }
@@ -320,11 +1027,29 @@ impl<'a> IntoIterator for &'a Info {
         if let Some(ref t) = self.tag_distance_ltrimv {
             vec.push(("tag_distance_ltrimv", t));
         }
-        if let Some(ref t) = self.rust_crate_version {
-            vec.push(("rust_crate_version", t));
+        if let Some(ref t) = self.version_major {
+            vec.push(("version_major", t));
+        }
+        if let Some(ref t) = self.version_minor {
+            vec.push(("version_minor", t));
         }
-        if let Some(ref t) = self.python_module_version {
-            vec.push(("python_module_version", t));
+        if let Some(ref t) = self.version_patch {
+            vec.push(("version_patch", t));
+        }
+        if let Some(ref t) = self.version_prerelease {
+            vec.push(("version_prerelease", t));
+        }
+        if let Some(ref t) = self.version_build {
+            vec.push(("version_build", t));
+        }
+        if let Some(ref v) = self.version_is_semver {
+            vec.push(("version_is_semver", bool2str(*v)));
+        }
+        if let Some(ref t) = self.docker_tags {
+            vec.push(("docker_tags", t));
+        }
+        if let Some(ref t) = self.docker_tags_lines {
+            vec.push(("docker_tags_lines", t));
         }
         if let Some(ref t) = self.version_mismatch {
             vec.push(("version_mismatch", t));
@@ -335,6 +1060,9 @@ impl<'a> IntoIterator for &'a Info {
         if let Some(ref t) = self.version_commit {
             vec.push(("version_commit", t));
         }
+        if let Some(ref t) = self.version_pr {
+            vec.push(("version_pr", t));
+        }
         if let Some(ref t) = self.override_version_tagged {
             vec.push(("override_version_tagged", t));
         }
@@ -344,50 +1072,461 @@ impl<'a> IntoIterator for &'a Info {
         if let Some(ref t) = self.override_version_docker_ci {
             vec.push(("override_version_docker_ci", t));
         }
+        let mut vec: Vec<(String, &'a str)> =
+            vec.into_iter().map(|(k, v)| (k.to_string(), v)).collect();
+        for (k, v) in &self.sources_outputs {
+            vec.push((k.clone(), v));
+        }
         vec.into_iter()
     }
 }
 
-fn write_github_output(output_filename: &Path, info: &Info) -> Result<()> {
+/// Comma-separated list as an array
+fn json_list(v: &Option<String>) -> Option<Vec<&str>> {
+    v.as_ref()
+        .map(|v| v.split(',').filter(|s| !s.is_empty()).collect())
+}
+
+fn json_number(v: &Option<String>) -> Option<u64> {
+    v.as_ref().and_then(|v| v.parse().ok())
+}
+
+/// The outputs with typed values: booleans, numbers and arrays, with
+/// `null` for the ones that are not defined.
+impl Serialize for Info {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("name", &self.name)?;
+        map.serialize_entry("pwd_basename", &self.pwd_basename)?;
+        map.serialize_entry("commit", &self.commit)?;
+        map.serialize_entry("git_describe_tags", &self.git_describe_tags)?;
+        map.serialize_entry("tag_latest", &self.tag_latest)?;
+        map.serialize_entry("version_docker_ci", &self.version_docker_ci)?;
+        map.serialize_entry("rpm_basename", &self.rpm_basename)?;
+        map.serialize_entry("deb_basename", &self.deb_basename)?;
+        map.serialize_entry("is_push", &self.is_push)?;
+        map.serialize_entry("is_tag", &self.is_tag)?;
+        map.serialize_entry("is_main", &self.is_main)?;
+        map.serialize_entry("is_push_tag", &self.is_push_tag)?;
+        map.serialize_entry("is_push_main", &self.is_push_main)?;
+        map.serialize_entry("is_release", &self.is_release)?;
+        map.serialize_entry("is_prerelease", &self.is_prerelease)?;
+        map.serialize_entry("is_draft", &self.is_draft)?;
+        map.serialize_entry("is_tag_release", &self.is_tag_release)?;
+        map.serialize_entry("is_workflow_dispatch", &self.is_workflow_dispatch)?;
+        map.serialize_entry("is_created", &self.is_created)?;
+        map.serialize_entry("is_deleted", &self.is_deleted)?;
+        map.serialize_entry("is_forced", &self.is_forced)?;
+        map.serialize_entry("pr_labels", &json_list(&self.pr_labels))?;
+        map.serialize_entry("tag_status", &self.tag_status.map(|s| s.as_str()))?;
+        map.serialize_entry("is_pull_request", &self.is_pull_request)?;
+        map.serialize_entry("pr_number", &json_number(&self.pr_number))?;
+        map.serialize_entry("pr_head_ref", &self.pr_head_ref)?;
+        map.serialize_entry("pr_base_ref", &self.pr_base_ref)?;
+        map.serialize_entry("is_release_branch", &self.is_release_branch)?;
+        map.serialize_entry("is_push_release_branch", &self.is_push_release_branch)?;
+        map.serialize_entry("commit_main", &self.commit_main)?;
+        map.serialize_entry("is_main_here", &self.is_main_here)?;
+        map.serialize_entry("tag_head", &self.tag_head)?;
+        map.serialize_entry("tag_head_ltrimv", &self.tag_head_ltrimv)?;
+        map.serialize_entry("distance", &json_number(&self.distance))?;
+        map.serialize_entry("dash_distance", &self.dash_distance)?;
+        map.serialize_entry("tag_distance", &self.tag_distance)?;
+        map.serialize_entry("tag_latest_ltrimv", &self.tag_latest_ltrimv)?;
+        map.serialize_entry("tag_distance_ltrimv", &self.tag_distance_ltrimv)?;
+        map.serialize_entry("version_major", &json_number(&self.version_major))?;
+        map.serialize_entry("version_minor", &json_number(&self.version_minor))?;
+        map.serialize_entry("version_patch", &json_number(&self.version_patch))?;
+        map.serialize_entry("version_prerelease", &self.version_prerelease)?;
+        map.serialize_entry("version_build", &self.version_build)?;
+        map.serialize_entry("version_is_semver", &self.version_is_semver)?;
+        map.serialize_entry("docker_tags", &json_list(&self.docker_tags))?;
+        map.serialize_entry("version_mismatch", &self.version_mismatch)?;
+        map.serialize_entry("version_mismatches", &self.version_mismatches)?;
+        map.serialize_entry("version_mismatch_count", &self.version_mismatches.len())?;
+        map.serialize_entry("version_tagged", &self.version_tagged)?;
+        map.serialize_entry("version_commit", &self.version_commit)?;
+        map.serialize_entry("version_pr", &self.version_pr)?;
+        map.serialize_entry("override_version_tagged", &self.override_version_tagged)?;
+        map.serialize_entry("override_version_commit", &self.override_version_commit)?;
+        map.serialize_entry(
+            "override_version_docker_ci",
+            &self.override_version_docker_ci,
+        )?;
+        map.serialize_entry("tags", &self.tags)?;
+        map.serialize_entry("warnings", &self.warnings)?;
+        for (k, v) in &self.sources_outputs {
+            map.serialize_entry(k, v)?;
+        }
+        map.end()
+    }
+}
+
+/// Checks that the outputs don't have control characters other than
+/// newlines and tabs; a carriage return, for instance, ends the line
+/// in the github output file as well.
+fn check_outputs<'a>(
+    outputs: impl Iterator<Item = (String, &'a str)>,
+) -> Result<Vec<(String, &'a str)>> {
+    let outputs = outputs.collect::<Vec<_>>();
+    for (k, v) in &outputs {
+        if v.chars().any(|c| c.is_control() && c != '\n' && c != '\t') {
+            bail!("Output {k} has a control character: {v:?}");
+        }
+    }
+    Ok(outputs)
+}
+
+/// Random delimiter for a multiline value in the github output file,
+/// so that the value can't end the heredoc and inject other outputs.
+fn heredoc_delimiter(value: &str) -> String {
+    loop {
+        let random = RandomState::new().build_hasher().finish();
+        let delimiter = format!("ghadelimiter_{random:016x}");
+        if !value.contains(&delimiter) {
+            return delimiter;
+        }
+    }
+}
+
+/// Appends the outputs to the github output file, using the heredoc
+/// syntax for multiline values; nothing is written if any of them
+/// has a control character.
+pub fn write_github_output<'a>(
+    output_filename: &Path,
+    outputs: impl Iterator<Item = (String, &'a str)>,
+) -> Result<()> {
+    let outputs = check_outputs(outputs)?;
     let mut output = fs::File::options().append(true).open(output_filename)?;
-    for (k, v) in info {
+    for (k, v) in outputs {
+        if v.contains('\n') {
+            let delimiter = heredoc_delimiter(v);
+            writeln!(output, "{k}<<{delimiter}")?;
+            writeln!(output, "{v}")?;
+            writeln!(output, "{delimiter}")?;
+        } else {
+            writeln!(output, "{k}={v}")?;
+        }
+    }
+    Ok(())
+}
+
+/// Valid name of a dotenv variable
+fn is_dotenv_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Writes the outputs in the dotenv format used by the gitlab
+/// `reports: dotenv` artifacts, which doesn't support multiline values.
+/// The `json` output and outputs with names that are not valid
+/// variable names, like the ones of some crates, are left out.
+pub fn write_dotenv<'a>(
+    output_filename: &Path,
+    outputs: impl Iterator<Item = (String, &'a str)>,
+) -> Result<()> {
+    let outputs = outputs.filter(|(k, _)| k != "json" && is_dotenv_name(k));
+    let outputs = check_outputs(outputs)?;
+    let mut output = fs::File::create(output_filename)?;
+    for (k, v) in outputs.into_iter().filter(|(_, v)| !v.contains('\n')) {
         writeln!(output, "{k}={v}")?;
     }
     Ok(())
 }
 
-pub fn process_repo(repo: Option<&Path>) -> Result<()> {
+pub fn process_repo(args: &Args) -> Result<()> {
     let curr_dir = env::current_dir()?;
-    let workspace = if let Some(path) = repo {
+    let workspace = if let Some(ref path) = args.repo {
         path
     } else {
         &curr_dir
     };
-    let info = Info::from_workspace(workspace, env::vars())?;
-    for (k, v) in &info {
-        println!("Setting {k}={v}");
+    let config = config::Config::load(workspace, args.config.as_deref())?;
+    let vars = env::vars().collect::<Vec<_>>();
+    let provider = ci::Provider::detect(&vars);
+    let enviter = provider
+        .github_vars(&vars)
+        .into_iter()
+        .chain(vars)
+        .chain(args.env_overrides());
+    let info = Info::from_workspace(workspace, &config, enviter)?;
+    let only = if args.only.is_empty() {
+        config.outputs.only.as_deref().unwrap_or_default()
+    } else {
+        &args.only
+    };
+    let is_selected = |k: &str| only.is_empty() || only.iter().any(|o| o == k);
+    let json = serde_json::to_value(&info)?;
+    let json_output = json.to_string();
+    let selected = || {
+        (&info)
+            .into_iter()
+            .chain(iter::once(("json".to_string(), json_output.as_str())))
+            .filter(|(k, _)| is_selected(k))
+    };
+    if !args.quiet {
+        match args.format {
+            Format::Text => {
+                for (k, v) in selected() {
+                    println!("Setting {k}={v}");
+                }
+            }
+            Format::Json => {
+                let mut json = json.clone();
+                if let Some(map) = json.as_object_mut() {
+                    map.retain(|k, _| is_selected(k));
+                }
+                println!("{}", serde_json::to_string_pretty(&json)?);
+            }
+        }
     }
-    if let Ok(output_filename) = env::var("GITHUB_OUTPUT") {
-        write_github_output(Path::new(&output_filename), &info)?;
+    // Annotations go to stderr when stdout has the json
+    let annotate = |annotation: &Annotation, level: &str| {
+        let line = provider.annotation(annotation, level);
+        match args.format {
+            Format::Text => println!("{line}"),
+            Format::Json => eprintln!("{line}"),
+        }
+    };
+    // The github output file is only used in local mode if given
+    // explicitly.
+    if !args.no_github_output && (!info.local || args.github_output.is_some()) {
+        let output_filename = args
+            .github_output
+            .clone()
+            .or_else(|| env::var_os("GITHUB_OUTPUT").map(PathBuf::from));
+        if let Some(output_filename) = output_filename {
+            write_github_output(&output_filename, selected())?;
+        }
+    }
+    if let Some(ref dotenv) = args.dotenv {
+        let outputs =
+            selected().filter(|(k, _)| !only.is_empty() || DOTENV_DEFAULT.contains(&k.as_str()));
+        write_dotenv(dotenv, outputs)?;
+    }
+    let env_names = if args.env.is_empty() {
+        config.outputs.env.as_deref().unwrap_or_default()
+    } else {
+        &args.env
+    };
+    let env_prefix = args
+        .env_prefix
+        .as_deref()
+        .or(config.outputs.env_prefix.as_deref())
+        .unwrap_or(ENV_PREFIX_DEFAULT);
+    if !env_prefix
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        bail!("Invalid prefix for the environment variables: {env_prefix:?}");
     }
-    if let Some(ref message) = info.version_mismatch {
-        if info.is_push_tag == Some(true) {
-            println!("::error {message}");
-            bail!("Version mismatch while pushing tag");
+    // Same rules as the github output file for the other github files
+    let github_file = |arg: &Option<PathBuf>, var: &str| {
+        if info.local && arg.is_none() {
+            None
         } else {
-            println!("::warning {message}");
+            arg.clone().or_else(|| env::var_os(var).map(PathBuf::from))
         }
+    };
+    if !env_names.is_empty()
+        && let Some(env_filename) = github_file(&args.github_env, "GITHUB_ENV")
+    {
+        let exported = (&info)
+            .into_iter()
+            .chain(iter::once(("json".to_string(), json_output.as_str())))
+            .filter(|(k, _)| env_names.contains(k))
+            .map(|(k, v)| (format!("{env_prefix}{}", k.to_uppercase()), v));
+        write_github_output(&env_filename, exported)?;
+    }
+    if !args.no_step_summary
+        && config.outputs.step_summary != Some(false)
+        && let Some(summary_filename) = github_file(&args.step_summary, "GITHUB_STEP_SUMMARY")
+    {
+        let mut summary = fs::File::options().append(true).open(summary_filename)?;
+        write!(summary, "{}", info.step_summary())?;
+    }
+    for warning in &info.warnings {
+        annotate(warning, "warning");
+    }
+    let fail = info.is_tag_release == Some(true)
+        && config.on_mismatch == config::MismatchPolicy::Fail
+        && !info.version_mismatches.is_empty();
+    if config.on_mismatch != config::MismatchPolicy::Ignore {
+        for mismatch in &info.version_mismatches {
+            if fail {
+                annotate(mismatch, "error");
+            } else {
+                annotate(mismatch, "warning");
+            }
+        }
+    }
+    if fail {
+        bail!(
+            "Version mismatch while releasing tag ({} found)",
+            info.version_mismatches.len()
+        );
     }
     Ok(())
 }
 
-#[derive(Parser, Debug)]
+/// Default prefix of the environment variables exported to the next
+/// steps
+pub const ENV_PREFIX_DEFAULT: &str = "VERSION_GEN_";
+
+/// Outputs written to the dotenv file when no selection is given, as
+/// gitlab limits the size and the number of variables of the reports
+pub const DOTENV_DEFAULT: &[&str] = &[
+    "version_tagged",
+    "version_commit",
+    "version_docker_ci",
+    "version_pr",
+    "version_mismatch",
+    "tag_latest",
+    "tag_distance",
+    "commit",
+    "is_tag_release",
+];
+
+/// Format of the outputs printed to stdout
+#[derive(ValueEnum, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// `Setting <name>=<value>` lines
+    #[default]
+    Text,
+    /// A JSON object with typed values
+    Json,
+}
+
+#[derive(Parser, Debug, Default)]
 #[command(author, version, about, long_about = None)]
-pub struct Args {}
+pub struct Args {
+    /// Path of the git repository [default: current directory]
+    #[arg(short = 'C', long)]
+    pub repo: Option<PathBuf>,
+    /// Configuration file [default: .github/version-gen.toml in the repository]
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+    /// Event name to use instead of GITHUB_EVENT_NAME
+    #[arg(long)]
+    pub event_name: Option<String>,
+    /// Git ref to use instead of GITHUB_REF (e.g. refs/tags/v1.0.0)
+    #[arg(long = "ref")]
+    pub git_ref: Option<String>,
+    /// File where outputs are appended [default: $GITHUB_OUTPUT]
+    #[arg(long)]
+    pub github_output: Option<PathBuf>,
+    /// Don't write the outputs to the github output file
+    #[arg(long, conflicts_with = "github_output")]
+    pub no_github_output: bool,
+    /// File where the exported variables are appended [default: $GITHUB_ENV]
+    #[arg(long)]
+    pub github_env: Option<PathBuf>,
+    /// Outputs exported as environment variables to the next steps
+    #[arg(long, value_delimiter = ',')]
+    pub env: Vec<String>,
+    /// Prefix of the exported environment variables [default: VERSION_GEN_]
+    #[arg(long)]
+    pub env_prefix: Option<String>,
+    /// File where the summary is appended [default: $GITHUB_STEP_SUMMARY]
+    #[arg(long)]
+    pub step_summary: Option<PathBuf>,
+    /// Don't write the summary
+    #[arg(long, conflicts_with = "step_summary")]
+    pub no_step_summary: bool,
+    /// File where the outputs are written in the dotenv format (gitlab)
+    #[arg(long)]
+    pub dotenv: Option<PathBuf>,
+    /// Infer the event from the repository and don't use the network
+    #[arg(long)]
+    pub local: bool,
+    /// Don't print the outputs to stdout
+    #[arg(short, long)]
+    pub quiet: bool,
+    /// Format of the outputs printed to stdout
+    #[arg(long, value_enum, default_value_t)]
+    pub format: Format,
+    /// Only emit the given outputs (comma-separated, can be repeated)
+    #[arg(long, value_delimiter = ',')]
+    pub only: Vec<String>,
+    /// Only consider tags that match the glob (can be repeated)
+    #[arg(long)]
+    pub tag_include: Vec<String>,
+    /// Ignore tags that match the glob (can be repeated)
+    #[arg(long)]
+    pub tag_exclude: Vec<String>,
+    /// Consider all tags, not only the ones that look like versions
+    #[arg(long)]
+    pub all_tags: bool,
+    /// Names of the trunk branches (can be repeated) [default: main,master]
+    #[arg(long, value_delimiter = ',')]
+    pub main_branch: Vec<String>,
+    /// Globs of the maintenance branches (can be repeated)
+    #[arg(long, value_delimiter = ',')]
+    pub release_branch: Vec<String>,
+    /// Name of the workflow_dispatch input with the version [default: version]
+    #[arg(long)]
+    pub dispatch_input: Option<String>,
+    /// Component of a monorepo, with tags like <COMPONENT>/v1.0.0
+    #[arg(long)]
+    pub component: Option<String>,
+    /// Directory of the component's manifests [default: <COMPONENT>]
+    #[arg(long, requires = "component")]
+    pub component_dir: Option<PathBuf>,
+}
+
+impl Args {
+    /// Environment variables that override the ones we got from the
+    /// process, as they are parsed last.
+    pub fn env_overrides(&self) -> Vec<(String, String)> {
+        let mut vars = vec![];
+        if let Some(ref event_name) = self.event_name {
+            vars.push(("GITHUB_EVENT_NAME".into(), event_name.clone()));
+        }
+        if let Some(ref git_ref) = self.git_ref {
+            vars.push(("GITHUB_REF".into(), git_ref.clone()));
+        }
+        if !self.tag_include.is_empty() {
+            vars.push(("VERSION_GEN_TAG_INCLUDE".into(), self.tag_include.join(",")));
+        }
+        if !self.tag_exclude.is_empty() {
+            vars.push(("VERSION_GEN_TAG_EXCLUDE".into(), self.tag_exclude.join(",")));
+        }
+        if self.local {
+            vars.push(("VERSION_GEN_LOCAL_MODE".into(), "true".into()));
+        }
+        if self.all_tags {
+            vars.push(("VERSION_GEN_TAG_VERSIONS_ONLY".into(), "false".into()));
+        }
+        if !self.main_branch.is_empty() {
+            vars.push((
+                "VERSION_GEN_MAIN_BRANCHES".into(),
+                self.main_branch.join(","),
+            ));
+        }
+        if !self.release_branch.is_empty() {
+            vars.push((
+                "VERSION_GEN_RELEASE_BRANCHES".into(),
+                self.release_branch.join(","),
+            ));
+        }
+        if let Some(ref dispatch_input) = self.dispatch_input {
+            vars.push(("VERSION_GEN_DISPATCH_INPUT".into(), dispatch_input.clone()));
+        }
+        if let Some(ref component) = self.component {
+            vars.push(("VERSION_GEN_COMPONENT".into(), component.clone()));
+        }
+        if let Some(ref component_dir) = self.component_dir {
+            vars.push((
+                "VERSION_GEN_COMPONENT_DIR".into(),
+                component_dir.display().to_string(),
+            ));
+        }
+        vars
+    }
+}
 
 pub fn main() -> Result<(), Box<dyn Error>> {
     color_eyre::install()?;
-    Args::parse();
-    process_repo(None)?;
+    let args = Args::parse();
+    process_repo(&args)?;
     Ok(())
 }
@@ -0,0 +1,189 @@
+// Copyright (C) 2026 Leandro Lisboa Penz <lpenz@lpenz.org>
+// This file is subject to the terms and conditions defined in
+// file 'LICENSE', which is part of this source code package.
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::path::PathBuf;
+
+use color_eyre::Result;
+use color_eyre::eyre::OptionExt;
+use color_eyre::eyre::eyre;
+
+use serde_json::Value;
+
+use crate::source::Position;
+use crate::source::VersionSource;
+use crate::source::glob_relative;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Package {
+    pub name: String,
+    /// Packages that are not published usually don't have a version
+    pub version: Option<String>,
+    /// Path of the package.json, relative to the repository
+    pub manifest: PathBuf,
+    /// True if the package has `"private": true`
+    pub private: bool,
+    /// Position of the version key in the package.json
+    pub position: Option<Position>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Workspace {
+    /// All packages found, starting with the primary one: the root
+    /// package, or the first member if the root has no name
+    pub packages: Vec<Package>,
+}
+
+fn read_json(manifest: &Path) -> Result<Option<(String, Value)>> {
+    match fs::read_to_string(manifest) {
+        Ok(contents) => match serde_json::from_str(&contents) {
+            Ok(json) => Ok(Some((contents, json))),
+            Err(e) => Err(eyre!("parsing {}: {}", manifest.display(), e)),
+        },
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Position of a key of the top-level object in a json document;
+/// serde_json doesn't keep track of spans.
+fn top_level_key_position(contents: &str, key: &str) -> Option<Position> {
+    let mut depth = 0;
+    let mut chars = contents.char_indices();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            '{' | '[' => depth += 1,
+            '}' | ']' => depth -= 1,
+            '"' => {
+                let mut string = String::new();
+                loop {
+                    match chars.next()? {
+                        (_, '\\') => {
+                            chars.next()?;
+                        }
+                        (_, '"') => break,
+                        (_, c) => string.push(c),
+                    }
+                }
+                if depth == 1 && string == key && chars.as_str().trim_start().starts_with(':') {
+                    return Some(Position::from_offset(contents, start));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The package declared in a package.json; `None` if it has no name,
+/// as in the ones that only have the configuration of the tooling.
+fn package(contents: &str, json: &Value, manifest: PathBuf) -> Result<Option<Package>> {
+    let Some(name) = json.get("name") else {
+        return Ok(None);
+    };
+    let name = name
+        .as_str()
+        .ok_or_eyre("could not convert name to string")?;
+    let version = match json.get("version") {
+        Some(v) => Some(
+            v.as_str()
+                .ok_or_eyre("could not convert version to string")?
+                .to_string(),
+        ),
+        None => None,
+    };
+    let private = json
+        .get("private")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+    let position = version
+        .as_ref()
+        .and_then(|_| top_level_key_position(contents, "version"));
+    Ok(Some(Package {
+        name: name.to_string(),
+        version,
+        manifest,
+        private,
+        position,
+    }))
+}
+
+/// Gets the workspaces globs, that can be either an array or an
+/// object with a `packages` array (yarn).
+fn workspace_patterns(json: &Value) -> Vec<&str> {
+    let workspaces = match json.get("workspaces") {
+        Some(Value::Object(o)) => o.get("packages"),
+        w => w,
+    };
+    workspaces
+        .and_then(Value::as_array)
+        .map(|a| a.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default()
+}
+
+pub fn workspace_data<P: AsRef<Path>>(repo: P) -> Result<Option<Workspace>> {
+    let repo = repo.as_ref();
+    let Some((contents, json)) = read_json(&repo.join("package.json"))? else {
+        return Ok(None);
+    };
+    let mut packages = vec![];
+    packages.extend(package(&contents, &json, "package.json".into())?);
+    let mut manifests = vec![];
+    for pattern in workspace_patterns(&json) {
+        for relative in glob_relative(repo, pattern)? {
+            // The globs usually match files too, e.g. packages/README.md
+            if !repo.join(&relative).is_dir() {
+                continue;
+            }
+            let manifest = relative.join("package.json");
+            if manifest == Path::new("package.json") || manifests.contains(&manifest) {
+                continue;
+            }
+            if let Some((contents, member)) = read_json(&repo.join(&manifest))? {
+                packages.extend(package(&contents, &member, manifest.clone())?);
+            }
+            manifests.push(manifest);
+        }
+    }
+    if packages.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(Workspace { packages }))
+}
+
+impl VersionSource for Package {
+    fn detect(repo: &Path) -> Result<Vec<Self>> {
+        Ok(workspace_data(repo)?
+            .map(|w| w.packages)
+            .unwrap_or_default())
+    }
+
+    fn prefix(&self) -> &'static str {
+        "node_package"
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    fn file(&self) -> &Path {
+        &self.manifest
+    }
+
+    fn position(&self) -> Option<Position> {
+        self.position
+    }
+
+    /// The root package is checked even if private, as that's usually
+    /// where applications keep their version.
+    fn checked(&self) -> bool {
+        !self.private || self.manifest == Path::new("package.json")
+    }
+}
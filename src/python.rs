@@ -3,6 +3,7 @@
 // file 'LICENSE', which is part of this source code package.
 
 use std::path::Path;
+use std::path::PathBuf;
 
 use color_eyre::Result;
 use color_eyre::eyre::OptionExt;
@@ -10,22 +11,204 @@ use color_eyre::eyre::eyre;
 
 use configparser::ini::Ini;
 
-#[derive(Debug, PartialEq, Eq)]
+use regex::Regex;
+
+use toml::Table;
+use toml::Value;
+
+use crate::source::Position;
+use crate::source::VersionSource;
+use crate::source::toml_position;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Data {
     pub name: String,
-    pub version: String,
+    /// The static version of the module; `None` if the version is
+    /// generated from the git tags at build time (setuptools-scm,
+    /// hatch-vcs, poetry-dynamic-versioning, etc.)
+    pub version: Option<String>,
+    /// File where the version is defined, relative to the repository
+    pub manifest: PathBuf,
+    /// Position of the version in the file where it's defined
+    pub position: Option<Position>,
 }
 
-pub fn module_data<P: AsRef<Path>>(repo: P) -> Result<Option<Data>> {
-    let setupcfgfile = repo.as_ref().join("setup.cfg");
-    let content = match std::fs::read_to_string(setupcfgfile) {
-        Ok(s) => s,
-        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-            return Ok(None);
-        }
-        Err(e) => {
-            return Err(eyre!(e));
+/// A version found in a file other than the manifest
+struct Found {
+    version: String,
+    file: PathBuf,
+    position: Position,
+}
+
+fn read_optional(path: &Path) -> Result<Option<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(s) => Ok(Some(s)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(eyre!(e)),
+    }
+}
+
+/// Reads `__version__ = "..."` from a python source file.
+fn source_version(repo: &Path, file: &Path) -> Result<Option<Found>> {
+    let Some(contents) = read_optional(&repo.join(file))? else {
+        return Ok(None);
+    };
+    let re = Regex::new(r#"(?m)^__version__\s*(?::\s*\w+\s*)?=\s*["']([^"']+)["']"#)?;
+    Ok(re.captures(&contents).map(|m| Found {
+        version: m[1].to_string(),
+        file: file.to_path_buf(),
+        position: Position::from_offset(&contents, m.get(0).unwrap().start()),
+    }))
+}
+
+/// Resolves the setuptools dynamic version directives, `attr:` and
+/// `file:`; `None` if the version can't be found statically, in which
+/// case it's not checked.
+fn setuptools_version(
+    repo: &Path,
+    attr: Option<&str>,
+    file: Option<&str>,
+) -> Result<Option<Found>> {
+    if let Some(file) = file {
+        return Ok(read_optional(&repo.join(file))?.map(|contents| Found {
+            version: contents.trim().to_string(),
+            file: file.into(),
+            position: Position { line: 1, col: 1 },
+        }));
+    }
+    let Some(module) = attr.and_then(|a| a.strip_suffix(".__version__")) else {
+        return Ok(None);
+    };
+    let module = module.replace('.', "/");
+    for candidate in [
+        format!("{module}/__init__.py"),
+        format!("src/{module}/__init__.py"),
+        format!("{module}.py"),
+        format!("src/{module}.py"),
+    ] {
+        if let Some(found) = source_version(repo, Path::new(&candidate))? {
+            return Ok(Some(found));
         }
+    }
+    Ok(None)
+}
+
+fn table<'a>(value: &'a Table, path: &[&str]) -> Option<&'a Table> {
+    path.iter()
+        .try_fold(value, |t, key| t.get(*key).and_then(Value::as_table))
+}
+
+/// PEP 621 `[project]` table, with `dynamic = ["version"]` resolved
+/// via hatch or setuptools when the version is in a file.
+fn pep621_data(repo: &Path, contents: &str, pyproject: &Table, project: &Table) -> Result<Data> {
+    let name = project
+        .get("name")
+        .and_then(Value::as_str)
+        .ok_or_eyre("could not find project.name")?
+        .to_string();
+    let is_dynamic = project
+        .get("dynamic")
+        .and_then(Value::as_array)
+        .is_some_and(|d| d.iter().any(|v| v.as_str() == Some("version")));
+    if !is_dynamic {
+        let version = project
+            .get("version")
+            .and_then(Value::as_str)
+            .ok_or_eyre("could not find project.version")?;
+        return Ok(Data {
+            name,
+            version: Some(version.to_string()),
+            manifest: "pyproject.toml".into(),
+            position: toml_position(contents, &["project", "version"]),
+        });
+    }
+    let mut found = None;
+    if let Some(hatch) = table(pyproject, &["tool", "hatch", "version"])
+        && let Some(path) = hatch.get("path").and_then(Value::as_str)
+    {
+        found = source_version(repo, Path::new(path))?;
+    } else if let Some(dynamic) = table(pyproject, &["tool", "setuptools", "dynamic"])
+        && let Some(version) = dynamic.get("version")
+    {
+        let file = match version.get("file") {
+            Some(Value::Array(files)) => files.first().and_then(Value::as_str),
+            Some(file) => file.as_str(),
+            None => None,
+        };
+        let attr = version.get("attr").and_then(Value::as_str);
+        found = setuptools_version(repo, attr, file)?;
+    }
+    // Anything else (hatch-vcs, setuptools-scm, pdm-backend...)
+    // generates the version from the git tags, or is not static.
+    Ok(match found {
+        Some(found) => Data {
+            name,
+            version: Some(found.version),
+            manifest: found.file,
+            position: Some(found.position),
+        },
+        None => Data {
+            name,
+            version: None,
+            manifest: "pyproject.toml".into(),
+            position: None,
+        },
+    })
+}
+
+fn poetry_data(contents: &str, pyproject: &Table, poetry: &Table) -> Result<Data> {
+    let name = poetry
+        .get("name")
+        .and_then(Value::as_str)
+        .ok_or_eyre("could not find tool.poetry.name")?
+        .to_string();
+    let is_dynamic = table(pyproject, &["tool", "poetry-dynamic-versioning"])
+        .and_then(|t| t.get("enable"))
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+    let version = if is_dynamic {
+        None
+    } else {
+        let version = poetry
+            .get("version")
+            .and_then(Value::as_str)
+            .ok_or_eyre("could not find tool.poetry.version")?;
+        Some(version.to_string())
+    };
+    let position = version
+        .as_ref()
+        .and_then(|_| toml_position(contents, &["tool", "poetry", "version"]));
+    Ok(Data {
+        name,
+        version,
+        manifest: "pyproject.toml".into(),
+        position,
+    })
+}
+
+pub fn pyproject_data<P: AsRef<Path>>(repo: P) -> Result<Option<Data>> {
+    let repo = repo.as_ref();
+    let Some(content) = read_optional(&repo.join("pyproject.toml"))? else {
+        return Ok(None);
+    };
+    let pyproject = content
+        .parse::<Table>()
+        .map_err(|e| eyre!("parsing pyproject.toml: {}", e))?;
+    if let Some(project) = table(&pyproject, &["project"]) {
+        pep621_data(repo, &content, &pyproject, project).map(Some)
+    } else if let Some(poetry) = table(&pyproject, &["tool", "poetry"]) {
+        poetry_data(&content, &pyproject, poetry).map(Some)
+    } else {
+        // pyproject.toml with only [build-system]; the metadata is
+        // in setup.cfg
+        Ok(None)
+    }
+}
+
+pub fn setupcfg_data<P: AsRef<Path>>(repo: P) -> Result<Option<Data>> {
+    let repo = repo.as_ref();
+    let Some(content) = read_optional(&repo.join("setup.cfg"))? else {
+        return Ok(None);
     };
     // Pre-parse the file to get rid of empty keys which are not
     // supported by configparser:
@@ -51,12 +234,88 @@ pub fn module_data<P: AsRef<Path>>(repo: P) -> Result<Option<Data>> {
             return Err(eyre!("parsing setup.cfg: {}", e));
         }
     }
-    Ok(Some(Data {
-        name: config
-            .get("metadata", "name")
-            .ok_or_eyre("could not find metadata.name")?,
-        version: config
-            .get("metadata", "version")
-            .ok_or_eyre("could not find metadata.version")?,
+    let name = config
+        .get("metadata", "name")
+        .ok_or_eyre("could not find metadata.name")?;
+    let version = config
+        .get("metadata", "version")
+        .ok_or_eyre("could not find metadata.version")?;
+    let found = if let Some(attr) = version.strip_prefix("attr:") {
+        setuptools_version(repo, Some(attr.trim()), None)?
+    } else if let Some(file) = version.strip_prefix("file:") {
+        setuptools_version(repo, None, Some(file.trim()))?
+    } else {
+        Some(Found {
+            version,
+            file: "setup.cfg".into(),
+            position: setupcfg_position(&content).unwrap_or(Position { line: 1, col: 1 }),
+        })
+    };
+    Ok(Some(match found {
+        Some(found) => Data {
+            name,
+            version: Some(found.version),
+            manifest: found.file,
+            position: Some(found.position),
+        },
+        None => Data {
+            name,
+            version: None,
+            manifest: "setup.cfg".into(),
+            position: None,
+        },
     }))
 }
+
+/// Position of the version key in the metadata section of setup.cfg
+fn setupcfg_position(content: &str) -> Option<Position> {
+    let mut in_metadata = false;
+    for (i, line) in content.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with('[') {
+            in_metadata = trimmed.trim_end() == "[metadata]";
+        } else if in_metadata
+            && let Some(key) = trimmed.split(['=', ':']).next()
+            && key.trim() == "version"
+        {
+            return Some(Position {
+                line: i + 1,
+                col: line.len() - trimmed.len() + 1,
+            });
+        }
+    }
+    None
+}
+
+pub fn module_data<P: AsRef<Path>>(repo: P) -> Result<Option<Data>> {
+    if let Some(data) = pyproject_data(&repo)? {
+        return Ok(Some(data));
+    }
+    setupcfg_data(&repo)
+}
+
+impl VersionSource for Data {
+    fn detect(repo: &Path) -> Result<Vec<Self>> {
+        Ok(module_data(repo)?.into_iter().collect())
+    }
+
+    fn prefix(&self) -> &'static str {
+        "python_module"
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    fn file(&self) -> &Path {
+        &self.manifest
+    }
+
+    fn position(&self) -> Option<Position> {
+        self.position
+    }
+}
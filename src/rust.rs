@@ -5,51 +5,238 @@
 use std::fs;
 use std::io::ErrorKind;
 use std::path::Path;
+use std::path::PathBuf;
 
 use color_eyre::Result;
 use color_eyre::eyre::OptionExt;
+use color_eyre::eyre::eyre;
 
 use toml::Table;
+use toml::Value;
 
-#[derive(Debug, PartialEq, Eq)]
+use crate::source::Position;
+use crate::source::VersionSource;
+use crate::source::glob_relative;
+use crate::source::toml_position;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Crate {
     pub name: String,
-    pub version: String,
+    /// Crates that are not published can omit the version
+    pub version: Option<String>,
+    /// Path of the crate's Cargo.toml, relative to the repository
+    pub manifest: PathBuf,
+    /// False if the crate has `publish = false` or no version
+    pub publish: bool,
+    /// True if the version comes from `workspace.package.version`
+    pub version_inherited: bool,
+    /// Position of the version key in the file where it's defined
+    pub position: Option<Position>,
 }
 
-pub fn crate_data<P: AsRef<Path>>(repo: P) -> Result<Option<Crate>> {
-    let cargofile = repo.as_ref().join("Cargo.toml");
-    let result = fs::read_to_string(cargofile);
-    if let Err(e) = result {
-        return if e.kind() == ErrorKind::NotFound {
-            Ok(None)
-        } else {
-            Err(e.into())
-        };
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Workspace {
+    /// All crates found, starting with the primary one: the root
+    /// package, the one designated in
+    /// `workspace.metadata.ghaction-version-gen.primary` or the first
+    /// member
+    pub crates: Vec<Crate>,
+}
+
+fn read_table(manifest: &Path) -> Result<Option<(String, Table)>> {
+    match fs::read_to_string(manifest) {
+        Ok(contents) => {
+            let table = contents.parse::<Table>()?;
+            Ok(Some((contents, table)))
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
     }
-    let contents = result.unwrap();
-    let info = contents.parse::<Table>()?;
-    if info.get("workspace").is_some() {
-        return Ok(None);
+}
+
+fn is_inherited(package: &Table, field: &str) -> bool {
+    package
+        .get(field)
+        .and_then(|v| v.get("workspace"))
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+}
+
+/// Gets a package field, resolving `field.workspace = true` from the
+/// `workspace.package` table.
+fn package_field<'a>(
+    package: &'a Table,
+    workspace_package: Option<&'a Table>,
+    field: &str,
+) -> Option<&'a Value> {
+    if is_inherited(package, field) {
+        workspace_package?.get(field)
+    } else {
+        package.get(field)
     }
-    let package = &info
-        .get("package")
-        .ok_or_eyre("could not find package section")?;
-    let mut name: String = package
+}
+
+/// The root Cargo.toml, where `workspace.package` is
+struct Root<'a> {
+    contents: &'a str,
+    workspace_package: Option<&'a Table>,
+}
+
+fn package_crate(root: &Root, contents: &str, package: &Table, manifest: PathBuf) -> Result<Crate> {
+    let workspace_package = root.workspace_package;
+    let name = package
         .get("name")
         .ok_or_eyre("could not find name in package section")?
-        .to_string();
-    if &name[0..1] == "\"" && &name[name.len() - 1..name.len()] == "\"" {
-        name = name[1..name.len() - 1].to_string();
-    }
-    let version_value = &package
-        .get("version")
-        .ok_or_eyre("could not find version in package section")?;
-    let version_str = version_value
         .as_str()
-        .ok_or_eyre("could not find convert version to string")?;
-    Ok(Some(Crate {
+        .ok_or_eyre("could not convert name to string")?;
+    let version = match package_field(package, workspace_package, "version") {
+        Some(v) => Some(
+            v.as_str()
+                .ok_or_eyre("could not convert version to string")?
+                .to_string(),
+        ),
+        None => None,
+    };
+    // Cargo implies `publish = false` when there's no version
+    let publish = match package_field(package, workspace_package, "publish") {
+        Some(Value::Boolean(b)) => *b,
+        Some(Value::Array(registries)) => !registries.is_empty(),
+        _ => version.is_some(),
+    };
+    let version_inherited = is_inherited(package, "version");
+    let position = if version.is_none() {
+        None
+    } else if version_inherited {
+        toml_position(root.contents, &["workspace", "package", "version"])
+    } else {
+        toml_position(contents, &["package", "version"])
+    };
+    Ok(Crate {
         name: name.to_string(),
-        version: version_str.to_string(),
-    }))
+        version,
+        manifest,
+        publish,
+        version_inherited,
+        position,
+    })
+}
+
+fn member_manifests(repo: &Path, workspace: &Table) -> Result<Vec<PathBuf>> {
+    let patterns = |key| -> Vec<&str> {
+        workspace
+            .get(key)
+            .and_then(Value::as_array)
+            .map(|a| a.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default()
+    };
+    let excludes = patterns("exclude");
+    let mut manifests = vec![];
+    for member in patterns("members") {
+        for relative in glob_relative(repo, member)? {
+            if excludes.iter().any(|e| relative == Path::new(e)) {
+                continue;
+            }
+            let manifest = relative.join("Cargo.toml");
+            if repo.join(&manifest).is_file() && !manifests.contains(&manifest) {
+                manifests.push(manifest);
+            }
+        }
+    }
+    Ok(manifests)
+}
+
+pub fn workspace_data<P: AsRef<Path>>(repo: P) -> Result<Option<Workspace>> {
+    let repo = repo.as_ref();
+    let Some((contents, info)) = read_table(&repo.join("Cargo.toml"))? else {
+        return Ok(None);
+    };
+    let Some(workspace) = info.get("workspace").and_then(Value::as_table) else {
+        let package = &info
+            .get("package")
+            .and_then(Value::as_table)
+            .ok_or_eyre("could not find package section")?;
+        let root = Root {
+            contents: &contents,
+            workspace_package: None,
+        };
+        let root_crate = package_crate(&root, &contents, package, "Cargo.toml".into())?;
+        return Ok(Some(Workspace {
+            crates: vec![root_crate],
+        }));
+    };
+    let root = Root {
+        contents: &contents,
+        workspace_package: workspace.get("package").and_then(Value::as_table),
+    };
+    let mut crates = vec![];
+    if let Some(package) = info.get("package").and_then(Value::as_table) {
+        crates.push(package_crate(
+            &root,
+            &contents,
+            package,
+            "Cargo.toml".into(),
+        )?);
+    }
+    for manifest in member_manifests(repo, workspace)? {
+        let (member_contents, member) = read_table(&repo.join(&manifest))?
+            .ok_or_else(|| eyre!("could not read {}", manifest.display()))?;
+        let package = member
+            .get("package")
+            .and_then(Value::as_table)
+            .ok_or_else(|| eyre!("could not find package section in {}", manifest.display()))?;
+        crates.push(package_crate(&root, &member_contents, package, manifest)?);
+    }
+    let designated = workspace
+        .get("metadata")
+        .and_then(|m| m.get("ghaction-version-gen"))
+        .and_then(|m| m.get("primary"))
+        .and_then(Value::as_str);
+    if let Some(primary) = designated
+        && info.get("package").is_none()
+    {
+        let index = crates
+            .iter()
+            .position(|c| c.name == primary)
+            .ok_or_else(|| eyre!("primary crate {primary} not found in workspace"))?;
+        let primary_crate = crates.remove(index);
+        crates.insert(0, primary_crate);
+    }
+    if crates.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(Workspace { crates }))
+}
+
+impl VersionSource for Crate {
+    fn detect(repo: &Path) -> Result<Vec<Self>> {
+        Ok(workspace_data(repo)?.map(|w| w.crates).unwrap_or_default())
+    }
+
+    fn prefix(&self) -> &'static str {
+        "rust_crate"
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    fn file(&self) -> &Path {
+        if self.version_inherited {
+            Path::new("Cargo.toml")
+        } else {
+            &self.manifest
+        }
+    }
+
+    fn position(&self) -> Option<Position> {
+        self.position
+    }
+
+    fn checked(&self) -> bool {
+        self.publish
+    }
 }
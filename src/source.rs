@@ -0,0 +1,116 @@
+// Copyright (C) 2026 Leandro Lisboa Penz <lpenz@lpenz.org>
+// This file is subject to the terms and conditions defined in
+// file 'LICENSE', which is part of this source code package.
+
+use std::fmt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use color_eyre::Result;
+
+use serde::Serialize;
+
+use crate::node;
+use crate::python;
+use crate::rust;
+
+/// Position in a file, both 1-based
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Position {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Position {
+    /// Position of the given byte offset in the contents
+    pub fn from_offset(contents: &str, offset: usize) -> Position {
+        let before = &contents[..offset];
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        Position {
+            line: before.matches('\n').count() + 1,
+            col: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+/// Paths that match a glob pattern relative to the repository, like
+/// the members of a workspace, also relative to it.
+pub fn glob_relative(repo: &Path, pattern: &str) -> Result<Vec<PathBuf>> {
+    // glob drops the leading `./` of the paths it returns, so we use
+    // the absolute path of the repository, escaped as it's not a
+    // pattern.
+    let root = repo.canonicalize()?;
+    let escaped = glob::Pattern::escape(&root.display().to_string());
+    glob::glob(&format!("{escaped}/{pattern}"))?
+        .map(|entry| Ok(entry?.strip_prefix(&root)?.to_path_buf()))
+        .collect()
+}
+
+/// Position of a key in a toml document, given by the path of
+/// tables that lead to it.
+pub fn toml_position(contents: &str, path: &[&str]) -> Option<Position> {
+    let document = toml::de::DeTable::parse(contents).ok()?;
+    let (last, tables) = path.split_last()?;
+    let mut table = document.get_ref();
+    for key in tables {
+        table = table.get(*key)?.get_ref().as_table()?;
+    }
+    let (key, _) = table.get_key_value(*last)?;
+    Some(Position::from_offset(contents, key.span().start))
+}
+
+/// A project manifest that declares a name and a version, like
+/// *Cargo.toml* or *package.json*.
+pub trait VersionSource: fmt::Debug {
+    /// Finds all the sources of this kind in the repository, with the
+    /// primary one first.
+    fn detect(repo: &Path) -> Result<Vec<Self>>
+    where
+        Self: Sized;
+
+    /// Prefix of the outputs, e.g. `rust_crate` for
+    /// `rust_crate_version`.
+    fn prefix(&self) -> &'static str;
+
+    fn name(&self) -> &str;
+
+    fn version(&self) -> Option<&str>;
+
+    /// File where the version is defined, relative to the repository
+    fn file(&self) -> &Path;
+
+    /// Position of the version in the file, if known
+    fn position(&self) -> Option<Position>;
+
+    /// Whether the version should match the tag
+    fn checked(&self) -> bool {
+        true
+    }
+}
+
+pub type Detector = fn(&Path) -> Result<Vec<Rc<dyn VersionSource>>>;
+
+fn detector<S: VersionSource + 'static>(repo: &Path) -> Result<Vec<Rc<dyn VersionSource>>> {
+    Ok(S::detect(repo)?
+        .into_iter()
+        .map(|s| Rc::new(s) as Rc<dyn VersionSource>)
+        .collect())
+}
+
+/// All the known sources, in order of precedence for the `name`
+/// output.
+pub const REGISTRY: &[Detector] = &[
+    detector::<rust::Crate>,
+    detector::<python::Data>,
+    detector::<node::Package>,
+];
+
+/// Runs all the detectors in the registry.
+pub fn detect_all<P: AsRef<Path>>(repo: P) -> Result<Vec<Rc<dyn VersionSource>>> {
+    let mut sources = vec![];
+    for detect in REGISTRY {
+        sources.extend(detect(repo.as_ref())?);
+    }
+    Ok(sources)
+}
@@ -0,0 +1,63 @@
+// Copyright (C) 2026 Leandro Lisboa Penz <lpenz@lpenz.org>
+// This file is subject to the terms and conditions defined in
+// file 'LICENSE', which is part of this source code package.
+
+//! Components of the versions in the tags
+
+use regex::Regex;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    pub major: u64,
+    /// Not present in short versions like `1`
+    pub minor: Option<u64>,
+    /// Not present in short versions like `1.2`
+    pub patch: Option<u64>,
+    pub prerelease: Option<String>,
+    pub build: Option<String>,
+    /// True if the version is a valid [SemVer](https://semver.org/)
+    pub is_semver: bool,
+}
+
+impl Version {
+    /// Parses a version with the `v` already stripped; versions that
+    /// are not SemVer, like `1.2` or `1.02`, are parsed leniently.
+    pub fn parse(s: &str) -> Option<Version> {
+        if let Ok(v) = semver::Version::parse(s) {
+            return Some(Version {
+                major: v.major,
+                minor: Some(v.minor),
+                patch: Some(v.patch),
+                prerelease: (!v.pre.is_empty()).then(|| v.pre.to_string()),
+                build: (!v.build.is_empty()).then(|| v.build.to_string()),
+                is_semver: true,
+            });
+        }
+        let re = Regex::new(
+            r"^(?P<major>\d+)(\.(?P<minor>\d+))?(\.(?P<patch>\d+))?(-(?P<prerelease>[0-9A-Za-z.-]+))?(\+(?P<build>[0-9A-Za-z.-]+))?$",
+        )
+        .unwrap();
+        let m = re.captures(s)?;
+        let number = |name| m.name(name).and_then(|n| n.as_str().parse::<u64>().ok());
+        let string = |name| m.name(name).map(|n| n.as_str().to_string());
+        Some(Version {
+            major: number("major")?,
+            minor: number("minor"),
+            patch: number("patch"),
+            prerelease: string("prerelease"),
+            build: string("build"),
+            is_semver: false,
+        })
+    }
+
+    /// True if this is not a pre-release
+    pub fn is_stable(&self) -> bool {
+        self.prerelease.is_none()
+    }
+
+    /// Major, minor and patch, for ordering; the missing ones count
+    /// as 0.
+    pub fn numbers(&self) -> (u64, u64, u64) {
+        (self.major, self.minor.unwrap_or(0), self.patch.unwrap_or(0))
+    }
+}
@@ -6,24 +6,49 @@ use std::env;
 use std::fs::File;
 use std::io::Write;
 use std::iter;
+use std::path::Path;
+use std::path::PathBuf;
 use std::process::Command;
 
 use color_eyre::Result;
 use color_eyre::eyre::ensure;
 
+use ghaction_version_gen::Annotation;
+use ghaction_version_gen::Args;
+use ghaction_version_gen::Format;
 use ghaction_version_gen::Info;
+use ghaction_version_gen::ci;
+use ghaction_version_gen::config::Config;
+use ghaction_version_gen::config::MismatchPolicy;
+use ghaction_version_gen::event::Event;
 use ghaction_version_gen::git;
+use ghaction_version_gen::git::TagFilter;
+use ghaction_version_gen::node;
 use ghaction_version_gen::python;
 use ghaction_version_gen::rust;
+use ghaction_version_gen::source::Position;
+use ghaction_version_gen::source::VersionSource;
+use ghaction_version_gen::version::Version;
 
 #[cfg(test)]
 fn environ_reset() {
     unsafe {
         env::remove_var("GITHUB_EVENT_NAME");
         env::remove_var("GITHUB_REF");
+        env::remove_var("GITHUB_STEP_SUMMARY");
     }
 }
 
+/// Environment variables in the form that `Info` takes
+fn env_vars<'a>(vars: &'a [(&str, &str)]) -> impl Iterator<Item = (String, String)> + 'a {
+    vars.iter().map(|(k, v)| (k.to_string(), v.to_string()))
+}
+
+/// Info of the repository, with the given environment
+fn info_env<P: AsRef<Path>>(repo: P, vars: &[(&str, &str)]) -> Result<Info> {
+    Info::from_workspace(repo, &Config::default(), env_vars(vars))
+}
+
 #[test]
 fn basic() -> Result<()> {
     environ_reset();
@@ -68,8 +93,16 @@ impl TmpGit {
         Ok(())
     }
 
+    /// Arguments that point to the repository
+    fn args(&self) -> Args {
+        Args {
+            repo: Some(self.repo.path().into()),
+            ..Args::default()
+        }
+    }
+
     fn info_get(&self) -> Result<Info> {
-        let mut info = Info::from_workspace(&self.repo, iter::empty())?;
+        let mut info = info_env(&self.repo, &[])?;
         info.is_push = None;
         info.is_tag = None;
         info.is_main = None;
@@ -115,6 +148,11 @@ fn gitrepo() -> Result<()> {
     assert_eq!(info.version_tagged, Some("1.0.0".to_string()));
     assert_eq!(info.version_commit, Some("1.0.0".to_string()));
     assert_eq!(info.version_docker_ci, "1.0.0");
+    assert_eq!(info.version_major, Some("1".to_string()));
+    assert_eq!(info.version_minor, Some("0".to_string()));
+    assert_eq!(info.version_patch, Some("0".to_string()));
+    assert_eq!(info.version_prerelease, None);
+    assert_eq!(info.version_is_semver, Some(true));
     // Check tag behind HEAD
     repo.file_write("bar.txt", "Hello again!")?;
     repo.run(&["git", "add", "bar.txt"])?;
@@ -175,7 +213,7 @@ fn gitrepo() -> Result<()> {
     assert_eq!(info.tag_latest_ltrimv, Some("7.5".to_string()));
     assert_eq!(info.distance, Some("1".to_string()));
     assert_eq!(info.version_docker_ci, "null");
-    ghaction_version_gen::process_repo(Some(repo.repo.as_ref()))?;
+    ghaction_version_gen::process_repo(&repo.args())?;
     Ok(())
 }
 
@@ -249,15 +287,18 @@ fn gitrepo_rust() -> Result<()> {
     info.is_main = Some(true);
     info.eval()?;
     assert_eq!(info.name, "test");
-    assert_eq!(info.rust_crate_version, Some("9.7".to_string()));
+    assert_eq!(
+        info.source("rust_crate").and_then(|s| s.version()),
+        Some("9.7")
+    );
     assert_eq!(
         info.version_mismatch,
-        Some("file=Cargo.toml::Version mismatch: tag 1.0.0 != 9.7 from Cargo.toml".to_string())
+        Some("Version mismatch: tag 1.0.0 != 9.7 from Cargo.toml".to_string())
     );
     assert_eq!(info.version_commit, Some("1.0.0".to_string()));
     assert_eq!(info.rpm_basename, "test-1.0.0");
     assert_eq!(info.deb_basename, "test_1.0.0");
-    ghaction_version_gen::process_repo(Some(repo.repo.as_ref()))?;
+    ghaction_version_gen::process_repo(&repo.args())?;
     Ok(())
 }
 
@@ -276,7 +317,10 @@ fn gitrepo_no_tag_rust() -> Result<()> {
     info.is_tag = Some(false);
     info.is_main = Some(true);
     info.eval()?;
-    assert_eq!(info.rust_crate_version, Some("9.7".to_string()));
+    assert_eq!(
+        info.source("rust_crate").and_then(|s| s.version()),
+        Some("9.7")
+    );
     assert_eq!(info.version_mismatch, None);
     assert_eq!(info.dash_distance, None);
     assert_eq!(info.tag_distance, None);
@@ -308,12 +352,15 @@ fn gitrepo_after_tag_rust() -> Result<()> {
     info.is_main = Some(false);
     info.eval()?;
     assert_eq!(info.name, "test");
-    assert_eq!(info.rust_crate_version, Some("9.7".to_string()));
+    assert_eq!(
+        info.source("rust_crate").and_then(|s| s.version()),
+        Some("9.7")
+    );
     assert_eq!(info.version_mismatch, None);
     assert_eq!(info.version_commit, None);
     assert_eq!(info.rpm_basename, format!("test-1.0.0-1-{}", info.commit));
     assert_eq!(info.deb_basename, format!("test_1.0.0-1-{}", info.commit));
-    ghaction_version_gen::process_repo(Some(repo.repo.as_ref()))?;
+    ghaction_version_gen::process_repo(&repo.args())?;
     Ok(())
 }
 
@@ -338,15 +385,18 @@ fn gitrepo_after_tag_rust_main() -> Result<()> {
     info.is_main = Some(true);
     info.eval()?;
     assert_eq!(info.name, "test");
-    assert_eq!(info.rust_crate_version, Some("9.7".to_string()));
+    assert_eq!(
+        info.source("rust_crate").and_then(|s| s.version()),
+        Some("9.7")
+    );
     assert_eq!(
         info.version_mismatch,
-        Some("file=Cargo.toml::Version mismatch: tag 1.0.0 != 9.7 from Cargo.toml".to_string())
+        Some("Version mismatch: tag 1.0.0 != 9.7 from Cargo.toml".to_string())
     );
     assert_eq!(info.version_commit, Some("1.0.0-1".into()));
     assert_eq!(info.rpm_basename, format!("test-1.0.0-1"));
     assert_eq!(info.deb_basename, format!("test_1.0.0-1"));
-    ghaction_version_gen::process_repo(Some(repo.repo.as_ref()))?;
+    ghaction_version_gen::process_repo(&repo.args())?;
     Ok(())
 }
 
@@ -354,22 +404,131 @@ fn gitrepo_after_tag_rust_main() -> Result<()> {
 fn toml1() -> Result<()> {
     environ_reset();
     let repo = TmpGit::new()?;
-    assert_eq!(rust::crate_data(&repo.repo)?, None);
+    assert_eq!(primary_source::<rust::Crate>(repo.repo.path())?, None);
     repo.file_write("Cargo.toml", "")?;
-    assert!(rust::crate_data(&repo.repo).is_err());
+    assert!(primary_source::<rust::Crate>(repo.repo.path()).is_err());
     repo.file_write("Cargo.toml", "[package]\n")?;
-    assert!(rust::crate_data(&repo.repo).is_err());
+    assert!(primary_source::<rust::Crate>(repo.repo.path()).is_err());
     repo.file_write(
         "Cargo.toml",
         "[package]\nname = \"test\"\nversion = \"1.0\"\n",
     )?;
-    let data = rust::crate_data(&repo.repo)?;
+    let data = primary_source::<rust::Crate>(repo.repo.path())?;
     assert!(data.is_some());
     let data = data.unwrap();
     assert_eq!(data.name, "test".to_string());
-    assert_eq!(data.version, "1.0".to_string());
+    assert_eq!(data.version, Some("1.0".to_string()));
+    assert!(data.publish);
+    // Crates without version are not published:
+    repo.file_write("Cargo.toml", "[package]\nname = \"test\"\n")?;
+    let data = primary_source::<rust::Crate>(repo.repo.path())?.unwrap();
+    assert_eq!(data.version, None);
+    assert!(!data.publish);
     repo.file_write("Cargo.toml", "[workspace]\nmembers = [ \"abc\" ]\n")?;
-    assert!(rust::crate_data(&repo.repo)?.is_none());
+    assert!(primary_source::<rust::Crate>(repo.repo.path())?.is_none());
+    Ok(())
+}
+
+#[test]
+fn toml_workspace() -> Result<()> {
+    environ_reset();
+    let repo = TmpGit::new()?;
+    std::fs::create_dir_all(repo.repo.path().join("crates/lib"))?;
+    std::fs::create_dir_all(repo.repo.path().join("crates/cli"))?;
+    std::fs::create_dir_all(repo.repo.path().join("xtask"))?;
+    repo.file_write(
+        "Cargo.toml",
+        "[workspace]\nmembers = [\"crates/*\", \"xtask\"]\n\
+         [workspace.package]\nversion = \"1.2.0\"\n",
+    )?;
+    repo.file_write(
+        "crates/lib/Cargo.toml",
+        "[package]\nname = \"mylib\"\nversion.workspace = true\n",
+    )?;
+    repo.file_write(
+        "crates/cli/Cargo.toml",
+        "[package]\nname = \"mycli\"\nversion = \"1.1.0\"\n",
+    )?;
+    repo.file_write(
+        "xtask/Cargo.toml",
+        "[package]\nname = \"xtask\"\nversion = \"0.1.0\"\npublish = false\n",
+    )?;
+    let workspace = rust::workspace_data(&repo.repo)?.unwrap();
+    assert_eq!(workspace.crates.len(), 3);
+    assert_eq!(workspace.crates[0].name, "mycli");
+    let mylib = workspace.crates.iter().find(|c| c.name == "mylib").unwrap();
+    assert_eq!(mylib.version.as_deref(), Some("1.2.0"));
+    assert_eq!(
+        mylib.manifest,
+        std::path::Path::new("crates/lib/Cargo.toml")
+    );
+    assert!(mylib.publish);
+    // The version is inherited, so it's defined in the root Cargo.toml:
+    assert_eq!(mylib.file(), std::path::Path::new("Cargo.toml"));
+    assert_eq!(mylib.position, Some(Position { line: 4, col: 1 }));
+    let mycli = workspace.crates.iter().find(|c| c.name == "mycli").unwrap();
+    assert_eq!(mycli.file(), std::path::Path::new("crates/cli/Cargo.toml"));
+    assert_eq!(mycli.position, Some(Position { line: 3, col: 1 }));
+    let xtask = workspace.crates.iter().find(|c| c.name == "xtask").unwrap();
+    assert!(!xtask.publish);
+    // Relative paths and paths with glob characters:
+    let relative = relative_path(repo.repo.path())?;
+    assert_eq!(rust::workspace_data(&relative)?.unwrap().crates.len(), 3);
+    let special = repo.repo.path().join("ws[1]");
+    std::fs::create_dir_all(special.join("crates/lib"))?;
+    std::fs::write(
+        special.join("Cargo.toml"),
+        "[workspace]\nmembers = [\"crates/*\"]\n",
+    )?;
+    std::fs::write(
+        special.join("crates/lib/Cargo.toml"),
+        "[package]\nname = \"mylib\"\nversion = \"1.0.0\"\n",
+    )?;
+    assert_eq!(rust::workspace_data(&special)?.unwrap().crates.len(), 1);
+    std::fs::remove_dir_all(&special)?;
+    // Designate the primary crate:
+    repo.file_write(
+        "Cargo.toml",
+        "[workspace]\nmembers = [\"crates/*\", \"xtask\"]\n\
+         [workspace.package]\nversion = \"1.2.0\"\n\
+         [workspace.metadata.ghaction-version-gen]\nprimary = \"mylib\"\n",
+    )?;
+    assert_eq!(
+        primary_source::<rust::Crate>(repo.repo.path())?
+            .unwrap()
+            .name,
+        "mylib"
+    );
+    repo.run(&["git", "add", "."])?;
+    repo.run(&["git", "commit", "-m", "first commit"])?;
+    repo.run(&["git", "tag", "v1.2.0"])?;
+    let mut info = repo.info_get()?;
+    info.parse_files(&repo.repo)?;
+    info.is_push = Some(true);
+    info.is_tag = Some(true);
+    info.is_main = Some(true);
+    info.eval()?;
+    assert_eq!(info.name, "mylib");
+    assert_eq!(
+        info.source("rust_crate").and_then(|s| s.version()),
+        Some("1.2.0")
+    );
+    let outputs = (&info).into_iter().collect::<Vec<_>>();
+    assert!(outputs.contains(&("rust_crate_names".to_string(), "mylib mycli xtask")));
+    assert!(outputs.contains(&("rust_crate_version_mycli".to_string(), "1.1.0")));
+    // xtask is not published, so only mycli mismatches:
+    assert_eq!(
+        info.version_mismatch,
+        Some("Version mismatch: tag 1.2.0 != 1.1.0 from crates/cli/Cargo.toml".to_string())
+    );
+    // Members can omit the version:
+    repo.file_write("xtask/Cargo.toml", "[package]\nname = \"xtask\"\n")?;
+    info.parse_files(&repo.repo)?;
+    info.eval()?;
+    let xtask = info.sources.iter().find(|s| s.name() == "xtask").unwrap();
+    assert_eq!(xtask.version(), None);
+    assert!(!xtask.checked());
+    assert_eq!(info.version_mismatches.len(), 1);
     Ok(())
 }
 
@@ -397,13 +556,14 @@ package_dir =
     info.is_tag = Some(true);
     info.is_main = Some(true);
     info.eval()?;
-    assert_eq!(info.python_module_name, Some("myname".to_string()));
-    assert_eq!(info.python_module_version, Some("9.7".to_string()));
+    let source = info.source("python_module").unwrap();
+    assert_eq!(source.name(), "myname");
+    assert_eq!(source.version(), Some("9.7"));
     assert_eq!(
         info.version_mismatch,
-        Some("file=setup.cfg::Version mismatch: tag 1.0.0 != 9.7 from setup.cfg".to_string())
+        Some("Version mismatch: tag 1.0.0 != 9.7 from setup.cfg".to_string())
     );
-    ghaction_version_gen::process_repo(Some(repo.repo.as_ref()))?;
+    ghaction_version_gen::process_repo(&repo.args())?;
     Ok(())
 }
 
@@ -425,8 +585,1604 @@ fn setupcfg() -> Result<()> {
         python::module_data(&repo.repo)?,
         Some(python::Data {
             name: "abcde".to_string(),
-            version: "1.0".to_string()
+            version: Some("1.0".to_string()),
+            manifest: "setup.cfg".into(),
+            position: Some(Position { line: 3, col: 1 }),
+        })
+    );
+    Ok(())
+}
+
+#[test]
+fn pyproject() -> Result<()> {
+    environ_reset();
+    let repo = TmpGit::new()?;
+    // Only [build-system], falls back to setup.cfg:
+    repo.file_write("pyproject.toml", "[build-system]\nrequires = []\n")?;
+    assert_eq!(python::module_data(&repo.repo)?, None);
+    repo.file_write("setup.cfg", "[metadata]\nname = abcde\nversion = 1.0\n")?;
+    assert_eq!(
+        python::module_data(&repo.repo)?.unwrap().manifest,
+        std::path::Path::new("setup.cfg")
+    );
+    // PEP 621 takes precedence:
+    repo.file_write(
+        "pyproject.toml",
+        "[project]\nname = \"pep621\"\nversion = \"2.0\"\n",
+    )?;
+    assert_eq!(
+        python::module_data(&repo.repo)?,
+        Some(python::Data {
+            name: "pep621".to_string(),
+            version: Some("2.0".to_string()),
+            manifest: "pyproject.toml".into(),
+            position: Some(Position { line: 3, col: 1 }),
         })
     );
+    repo.file_write("pyproject.toml", "[project]\nname = \"pep621\"\n")?;
+    assert!(python::module_data(&repo.repo).is_err());
+    // Poetry:
+    repo.file_write(
+        "pyproject.toml",
+        "[tool.poetry]\nname = \"poetic\"\nversion = \"3.0\"\n",
+    )?;
+    let data = python::module_data(&repo.repo)?.unwrap();
+    assert_eq!(data.name, "poetic");
+    assert_eq!(data.version, Some("3.0".to_string()));
+    repo.file_write(
+        "pyproject.toml",
+        "[tool.poetry]\nname = \"poetic\"\nversion = \"0.0.0\"\n\
+         [tool.poetry-dynamic-versioning]\nenable = true\n",
+    )?;
+    assert_eq!(python::module_data(&repo.repo)?.unwrap().version, None);
+    // Dynamic version from the git tags:
+    repo.file_write(
+        "pyproject.toml",
+        "[project]\nname = \"scm\"\ndynamic = [\"version\"]\n\
+         [tool.setuptools_scm]\n",
+    )?;
+    assert_eq!(python::module_data(&repo.repo)?.unwrap().version, None);
+    // Dynamic version from a source file, via hatch:
+    std::fs::create_dir_all(repo.repo.path().join("src/hatched"))?;
+    repo.file_write("src/hatched/__about__.py", "__version__ = \"4.1\"\n")?;
+    repo.file_write(
+        "pyproject.toml",
+        "[project]\nname = \"hatched\"\ndynamic = [\"version\"]\n\
+         [tool.hatch.version]\npath = \"src/hatched/__about__.py\"\n",
+    )?;
+    let data = python::module_data(&repo.repo)?.unwrap();
+    assert_eq!(data.version, Some("4.1".to_string()));
+    assert_eq!(
+        data.manifest,
+        std::path::Path::new("src/hatched/__about__.py")
+    );
+    // Dynamic version from a module attribute, via setuptools:
+    repo.file_write("src/hatched/__init__.py", "__version__ = '4.2'\n")?;
+    repo.file_write(
+        "pyproject.toml",
+        "[project]\nname = \"hatched\"\ndynamic = [\"version\"]\n\
+         [tool.setuptools.dynamic]\nversion = { attr = \"hatched.__version__\" }\n",
+    )?;
+    let data = python::module_data(&repo.repo)?.unwrap();
+    assert_eq!(data.version, Some("4.2".to_string()));
+    assert_eq!(
+        data.manifest,
+        std::path::Path::new("src/hatched/__init__.py")
+    );
+    // Versions that can't be resolved statically are not checked:
+    for version in [
+        "{ attr = \"hatched.VERSION\" }",
+        "{ file = \"VERSION\" }",
+        "{}",
+    ] {
+        repo.file_write(
+            "pyproject.toml",
+            &format!(
+                "[project]\nname = \"hatched\"\ndynamic = [\"version\"]\n\
+                 [tool.setuptools.dynamic]\nversion = {version}\n"
+            ),
+        )?;
+        let data = python::module_data(&repo.repo)?.unwrap();
+        assert_eq!(data.version, None);
+        assert_eq!(data.manifest, std::path::Path::new("pyproject.toml"));
+    }
+    std::fs::remove_file(repo.repo.path().join("pyproject.toml"))?;
+    repo.file_write(
+        "setup.cfg",
+        "[metadata]\nname = hatched\nversion = attr: hatched.VERSION\n",
+    )?;
+    let data = python::module_data(&repo.repo)?.unwrap();
+    assert_eq!(data.version, None);
+    assert_eq!(data.manifest, std::path::Path::new("setup.cfg"));
+    Ok(())
+}
+
+#[test]
+fn process_args() -> Result<()> {
+    environ_reset();
+    let repo = TmpGit::new()?;
+    repo.file_write("foo.txt", "Hello, world!")?;
+    repo.run(&["git", "add", "foo.txt"])?;
+    repo.run(&["git", "commit", "-m", "first commit"])?;
+    repo.run(&["git", "tag", "v1.0.0"])?;
+    let output = tempfile::NamedTempFile::new()?;
+    let args = Args {
+        event_name: Some("push".into()),
+        git_ref: Some("refs/tags/v1.0.0".into()),
+        github_output: Some(output.path().into()),
+        quiet: true,
+        only: vec!["version_tagged".into(), "is_push_tag".into()],
+        ..repo.args()
+    };
+    ghaction_version_gen::process_repo(&args)?;
+    let contents = std::fs::read_to_string(output.path())?;
+    assert_eq!(contents, "is_push_tag=true\nversion_tagged=1.0.0\n");
+    Ok(())
+}
+
+#[test]
+fn docker_tags() -> Result<()> {
+    environ_reset();
+    let info_get = |describe: &str, tags: &[&str]| -> Result<Info> {
+        let mut info = Info {
+            is_push: Some(true),
+            is_tag: Some(true),
+            is_main: Some(false),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..Info::default()
+        };
+        info.parse_describe(describe)?;
+        info.eval()?;
+        Ok(info)
+    };
+    let info = info_get("v2.3.1", &["v1.0.0", "v2.3.1", "v3.0.0-rc1"])?;
+    assert_eq!(info.docker_tags, Some("2.3.1,2.3,2,latest".to_string()));
+    assert_eq!(
+        info.docker_tags_lines,
+        Some("2.3.1\n2.3\n2\nlatest".to_string())
+    );
+    // Maintenance release:
+    let info = info_get("v1.0.1", &["v1.0.0", "v1.0.1", "v2.3.1"])?;
+    assert_eq!(info.docker_tags, Some("1.0.1,1.0,1".to_string()));
+    // Maintenance release of an older minor, the major stays on 1.1.0:
+    let info = info_get("v1.0.1", &["v1.0.0", "v1.0.1", "v1.1.0", "v2.3.1"])?;
+    assert_eq!(info.docker_tags, Some("1.0.1,1.0".to_string()));
+    // Older patch of a minor, no floating tag moves:
+    let info = info_get("v1.1.0", &["v1.1.0", "v1.1.1"])?;
+    assert_eq!(info.docker_tags, Some("1.1.0".to_string()));
+    // Pre-release:
+    let info = info_get("v3.0.0-rc1", &["v2.3.1", "v3.0.0-rc1"])?;
+    assert_eq!(info.docker_tags, Some("3.0.0-rc1".to_string()));
+    // Not a tag:
+    let mut info = info_get("v2.3.1-2-gc5f7a99", &["v2.3.1"])?;
+    info.is_tag = Some(false);
+    info.eval()?;
+    assert_eq!(info.docker_tags, None);
+    info.is_main = Some(true);
+    info.eval()?;
+    assert_eq!(info.docker_tags, Some("latest".to_string()));
+    Ok(())
+}
+
+#[test]
+fn process_multiline() -> Result<()> {
+    environ_reset();
+    let repo = TmpGit::new()?;
+    repo.file_write("foo.txt", "Hello, world!")?;
+    repo.run(&["git", "add", "foo.txt"])?;
+    repo.run(&["git", "commit", "-m", "first commit"])?;
+    repo.run(&["git", "tag", "v1.2.0"])?;
+    let output = tempfile::NamedTempFile::new()?;
+    let args = Args {
+        event_name: Some("push".into()),
+        git_ref: Some("refs/tags/v1.2.0".into()),
+        github_output: Some(output.path().into()),
+        quiet: true,
+        only: vec!["docker_tags".into(), "docker_tags_lines".into()],
+        ..repo.args()
+    };
+    ghaction_version_gen::process_repo(&args)?;
+    let contents = std::fs::read_to_string(output.path())?;
+    assert!(contents.contains("\ndocker_tags_lines<<ghadelimiter_"));
+    assert_eq!(
+        github_output_parse(&contents),
+        vec![
+            ("docker_tags".into(), "1.2.0,1.2,1,latest".into()),
+            ("docker_tags_lines".into(), "1.2.0\n1.2\n1\nlatest".into()),
+        ]
+    );
+    Ok(())
+}
+
+/// Parses the github output file like the runner does
+fn github_output_parse(contents: &str) -> Vec<(String, String)> {
+    let mut outputs = vec![];
+    let mut lines = contents.lines();
+    while let Some(line) = lines.next() {
+        let heredoc = line.find("<<");
+        match line.find('=') {
+            Some(eq) if heredoc.is_none_or(|h| eq < h) => {
+                outputs.push((line[..eq].to_string(), line[eq + 1..].to_string()));
+            }
+            _ => {
+                let (key, delimiter) = line.split_once("<<").unwrap();
+                let value = lines
+                    .by_ref()
+                    .take_while(|l| *l != delimiter)
+                    .collect::<Vec<_>>();
+                outputs.push((key.to_string(), value.join("\n")));
+            }
+        }
+    }
+    outputs
+}
+
+#[test]
+fn github_output_hostile() -> Result<()> {
+    environ_reset();
+    let repo = TmpGit::new()?;
+    repo.file_write("foo.txt", "Hello, world!")?;
+    repo.run(&["git", "add", "foo.txt"])?;
+    repo.run(&["git", "commit", "-m", "first commit"])?;
+    let info_get = |tagged: &str| {
+        info_env(
+            &repo.repo,
+            &[
+                ("GITHUB_EVENT_NAME", "push"),
+                ("GITHUB_REF", "refs/heads/main"),
+                ("OVERRIDE_VERSION_TAGGED", tagged),
+            ],
+        )
+    };
+    for hostile in [
+        "1.0.0\ninjected=true",
+        "1.0.0\nghaction_version_gen_EOF\ninjected=true",
+        "1.0.0\ninjected<<EOF\ntrue\nEOF",
+    ] {
+        let info = info_get(hostile)?;
+        let output = tempfile::NamedTempFile::new()?;
+        ghaction_version_gen::write_github_output(output.path(), (&info).into_iter())?;
+        let contents = std::fs::read_to_string(output.path())?;
+        let outputs = github_output_parse(&contents);
+        assert!(outputs.iter().all(|(k, _)| k != "injected"));
+        assert!(outputs.contains(&("override_version_tagged".into(), hostile.into())));
+        assert_eq!(outputs.len(), (&info).into_iter().count());
+    }
+    // Other control characters are rejected, and nothing is written:
+    for hostile in ["1.0.0\rinjected=true", "1.0.0\0", "1.0.0\x1b[31m"] {
+        let info = info_get(hostile)?;
+        let output = tempfile::NamedTempFile::new()?;
+        let result = ghaction_version_gen::write_github_output(output.path(), (&info).into_iter());
+        assert!(result.is_err());
+        assert_eq!(std::fs::read_to_string(output.path())?, "");
+    }
+    Ok(())
+}
+
+#[test]
+fn json_output() -> Result<()> {
+    environ_reset();
+    let repo = TmpGit::new()?;
+    repo.file_write("foo.txt", "Hello, world!")?;
+    repo.run(&["git", "add", "foo.txt"])?;
+    repo.run(&["git", "commit", "-m", "first commit"])?;
+    repo.run(&["git", "tag", "v1.2.0"])?;
+    let info = info_env(
+        &repo.repo,
+        &[
+            ("GITHUB_EVENT_NAME", "push"),
+            ("GITHUB_REF", "refs/tags/v1.2.0"),
+        ],
+    )?;
+    let json = serde_json::to_value(&info)?;
+    assert_eq!(json["is_push_tag"], serde_json::json!(true));
+    assert_eq!(json["is_pull_request"], serde_json::json!(false));
+    assert_eq!(json["pr_number"], serde_json::Value::Null);
+    assert_eq!(json["version_tagged"], serde_json::json!("1.2.0"));
+    assert_eq!(json["version_major"], serde_json::json!(1));
+    assert_eq!(json["distance"], serde_json::json!(0));
+    assert_eq!(
+        json["docker_tags"],
+        serde_json::json!(["1.2.0", "1.2", "1", "latest"])
+    );
+    assert_eq!(json["tags"], serde_json::json!(["v1.2.0"]));
+    assert_eq!(json["version_mismatches"], serde_json::json!([]));
+    assert_eq!(json["tag_status"], serde_json::json!("found"));
+    // The json output has everything, even if not selected:
+    let output = tempfile::NamedTempFile::new()?;
+    let args = Args {
+        event_name: Some("push".into()),
+        git_ref: Some("refs/tags/v1.2.0".into()),
+        github_output: Some(output.path().into()),
+        format: Format::Json,
+        only: vec!["json".into()],
+        ..repo.args()
+    };
+    ghaction_version_gen::process_repo(&args)?;
+    let contents = std::fs::read_to_string(output.path())?;
+    let value = contents.strip_prefix("json=").unwrap();
+    let written = serde_json::from_str::<serde_json::Value>(value)?;
+    assert_eq!(written["docker_tags"], json["docker_tags"]);
+    assert_eq!(written["version_tagged"], json["version_tagged"]);
+    Ok(())
+}
+
+#[test]
+fn github_env_and_summary() -> Result<()> {
+    environ_reset();
+    let repo = TmpGit::new()?;
+    repo.file_write("foo.txt", "Hello, world!")?;
+    repo.run(&["git", "add", "foo.txt"])?;
+    repo.run(&["git", "commit", "-m", "first commit"])?;
+    repo.run(&["git", "tag", "v1.2.0"])?;
+    let github_env = tempfile::NamedTempFile::new()?;
+    let summary = tempfile::NamedTempFile::new()?;
+    let args = Args {
+        event_name: Some("push".into()),
+        git_ref: Some("refs/tags/v1.2.0".into()),
+        no_github_output: true,
+        quiet: true,
+        github_env: Some(github_env.path().into()),
+        env: vec!["version_tagged".into(), "docker_tags_lines".into()],
+        step_summary: Some(summary.path().into()),
+        ..repo.args()
+    };
+    ghaction_version_gen::process_repo(&args)?;
+    let contents = std::fs::read_to_string(github_env.path())?;
+    assert_eq!(
+        github_output_parse(&contents),
+        vec![
+            (
+                "VERSION_GEN_DOCKER_TAGS_LINES".into(),
+                "1.2.0\n1.2\n1\nlatest".into()
+            ),
+            ("VERSION_GEN_VERSION_TAGGED".into(), "1.2.0".into()),
+        ]
+    );
+    let contents = std::fs::read_to_string(summary.path())?;
+    assert!(contents.contains("| push | tag | true | found |\n"));
+    assert!(contents.contains("A tag is being released"));
+    assert!(contents.contains("| `docker_tags_lines` | 1.2.0<br>1.2<br>1<br>latest |\n"));
+    // Custom prefix, no summary:
+    let github_env = tempfile::NamedTempFile::new()?;
+    let args = Args {
+        git_ref: Some("refs/heads/feature".into()),
+        github_env: Some(github_env.path().into()),
+        env: vec!["version_tagged".into(), "tag_latest".into()],
+        env_prefix: Some("MY_".into()),
+        no_step_summary: true,
+        ..args
+    };
+    ghaction_version_gen::process_repo(&args)?;
+    let contents = std::fs::read_to_string(github_env.path())?;
+    assert_eq!(contents, "MY_TAG_LATEST=v1.2.0\n");
+    let args = Args {
+        env_prefix: Some("MY=".into()),
+        ..args
+    };
+    assert!(ghaction_version_gen::process_repo(&args).is_err());
+    Ok(())
+}
+
+#[test]
+fn step_summary() -> Result<()> {
+    let mut info = Info {
+        is_push: Some(true),
+        is_tag: Some(false),
+        is_release_branch: Some(true),
+        is_tag_release: Some(false),
+        tag_status: Some(git::TagStatus::Shallow),
+        version_mismatch_count: "1".into(),
+        ..Info::default()
+    };
+    info.version_mismatches.push(Annotation {
+        file: Some("Cargo.toml".into()),
+        position: Some(Position { line: 3, col: 11 }),
+        title: "Version mismatch".into(),
+        message: "crate <foo> has version 1.0|1".into(),
+    });
+    let summary = info.step_summary();
+    assert!(summary.contains("| push | release branch | false | shallow |\n"));
+    assert!(summary.contains("Not a release nor a push"));
+    assert!(summary.contains(
+        "#### Version mismatches\n\n\
+         - Cargo.toml:3: Version mismatch: crate &lt;foo&gt; has version 1.0\\|1\n"
+    ));
+    assert!(!summary.contains("#### Warnings"));
+    // Pushes of the trunk branch, over a tag, after it and without tags:
+    let repo = TmpGit::new()?;
+    repo.run(&["git", "commit", "-q", "--allow-empty", "-m", "first"])?;
+    let info_get = || {
+        info_env(
+            &repo.repo,
+            &[
+                ("GITHUB_EVENT_NAME", "push"),
+                ("GITHUB_REF", "refs/heads/main"),
+            ],
+        )
+    };
+    let summary = info_get()?.step_summary();
+    assert!(summary.contains("The trunk branch was pushed"));
+    assert!(summary.contains("No tag was found"));
+    repo.run(&["git", "tag", "v1.1.0"])?;
+    let summary = info_get()?.step_summary();
+    assert!(summary.contains("The trunk branch was pushed"));
+    assert!(summary.contains("already tagged with v1.1.0"));
+    assert!(!summary.contains("Not a release nor a push"));
+    repo.run(&["git", "commit", "-q", "--allow-empty", "-m", "second"])?;
+    let summary = info_get()?.step_summary();
+    assert!(summary.contains("The most recent tag is v1.1.0, 1 commit(s) behind"));
+    assert!(summary.contains("`version_commit` is 1.1.0-1."));
+    Ok(())
+}
+
+#[test]
+fn pull_request() -> Result<()> {
+    environ_reset();
+    let repo = TmpGit::new()?;
+    repo.file_write("foo.txt", "Hello, world!")?;
+    repo.run(&["git", "add", "foo.txt"])?;
+    repo.run(&["git", "commit", "-m", "first commit"])?;
+    repo.run(&["git", "tag", "v1.2.3"])?;
+    repo.file_write("bar.txt", "Hello again!")?;
+    repo.run(&["git", "add", "bar.txt"])?;
+    repo.run(&["git", "commit", "-m", "second commit"])?;
+    let info = info_env(
+        &repo.repo,
+        &[
+            ("GITHUB_EVENT_NAME", "pull_request"),
+            ("GITHUB_REF", "refs/pull/42/merge"),
+            ("GITHUB_HEAD_REF", "feature"),
+            ("GITHUB_BASE_REF", "main"),
+        ],
+    )?;
+    assert_eq!(info.is_push, Some(false));
+    assert_eq!(info.is_pull_request, Some(true));
+    assert_eq!(info.pr_number, Some("42".to_string()));
+    assert_eq!(info.pr_head_ref, Some("feature".to_string()));
+    assert_eq!(info.pr_base_ref, Some("main".to_string()));
+    assert_eq!(info.version_pr, Some("1.2.3-pr.42.1".to_string()));
+    assert_eq!(info.version_commit, None);
+    assert_eq!(info.version_docker_ci, "null");
+    // Push events don't have a PR version:
+    let info = info_env(
+        &repo.repo,
+        &[
+            ("GITHUB_EVENT_NAME", "push"),
+            ("GITHUB_REF", "refs/heads/main"),
+            ("GITHUB_HEAD_REF", ""),
+        ],
+    )?;
+    assert_eq!(info.is_pull_request, Some(false));
+    assert_eq!(info.pr_number, None);
+    assert_eq!(info.pr_head_ref, None);
+    assert_eq!(info.version_pr, None);
+    Ok(())
+}
+
+#[test]
+fn release() -> Result<()> {
+    environ_reset();
+    let repo = TmpGit::new()?;
+    repo.file_write("foo.txt", "Hello, world!")?;
+    repo.run(&["git", "add", "foo.txt"])?;
+    repo.run(&["git", "commit", "-m", "first commit"])?;
+    repo.run(&["git", "tag", "v2.0.0-rc1"])?;
+    let event = tempfile::NamedTempFile::new()?;
+    let info_get = |payload: &str| -> Result<Info> {
+        std::fs::write(event.path(), payload)?;
+        info_env(
+            &repo.repo,
+            &[
+                ("GITHUB_EVENT_NAME", "release"),
+                ("GITHUB_REF", "refs/tags/v2.0.0-rc1"),
+                ("GITHUB_EVENT_PATH", event.path().to_str().unwrap()),
+            ],
+        )
+    };
+    let info =
+        info_get(r#"{"action": "published", "release": {"prerelease": true, "draft": false}}"#)?;
+    assert_eq!(info.is_push_tag, Some(false));
+    assert_eq!(info.is_release, Some(true));
+    assert_eq!(info.is_prerelease, Some(true));
+    assert_eq!(info.is_draft, Some(false));
+    assert_eq!(info.is_tag_release, Some(true));
+    assert_eq!(info.version_tagged, Some("2.0.0-rc1".to_string()));
+    assert_eq!(info.version_docker_ci, "2.0.0-rc1");
+    let info =
+        info_get(r#"{"action": "created", "release": {"prerelease": false, "draft": true}}"#)?;
+    assert_eq!(info.is_draft, Some(true));
+    assert_eq!(info.is_tag_release, Some(false));
+    assert_eq!(info.version_tagged, None);
+    let info =
+        info_get(r#"{"action": "released", "release": {"prerelease": false, "draft": false}}"#)?;
+    assert_eq!(info.is_tag_release, Some(true));
+    assert_eq!(info.version_tagged, Some("2.0.0-rc1".to_string()));
+    let info =
+        info_get(r#"{"action": "deleted", "release": {"prerelease": false, "draft": false}}"#)?;
+    assert_eq!(info.is_release, Some(true));
+    assert_eq!(info.is_tag_release, Some(false));
+    assert_eq!(info.version_tagged, None);
+    assert_eq!(info.version_commit, None);
+    Ok(())
+}
+
+#[test]
+fn event_payload() -> Result<()> {
+    let event = Event::parse(
+        r#"{
+            "before": "6113728f27ae82c7b1a177c8d03f9e96e0adf246",
+            "after": "0000000000000000000000000000000000000000",
+            "created": false,
+            "deleted": true,
+            "forced": false,
+            "action": "published",
+            "commits": [{"id": "abc"}, {"id": "def"}],
+            "pull_request": {"labels": [{"name": "bug"}, {"name": "preview"}]},
+            "inputs": {"version": "1.2.3", "dry_run": true}
+        }"#,
+    )?;
+    assert_eq!(event.deleted, Some(true));
+    assert_eq!(event.forced, Some(false));
+    assert_eq!(event.action.as_deref(), Some("published"));
+    assert_eq!(event.labels, vec!["bug", "preview"]);
+    assert_eq!(
+        event.inputs,
+        vec![
+            ("dry_run".to_string(), "true".to_string()),
+            ("version".to_string(), "1.2.3".to_string())
+        ]
+    );
+    assert_eq!(Event::parse("{}")?, Event::default());
+    assert!(Event::parse("{").is_err());
+    Ok(())
+}
+
+#[test]
+fn push_deleted() -> Result<()> {
+    environ_reset();
+    let repo = TmpGit::new()?;
+    repo.file_write("foo.txt", "Hello, world!")?;
+    repo.run(&["git", "add", "foo.txt"])?;
+    repo.run(&["git", "commit", "-m", "first commit"])?;
+    repo.run(&["git", "tag", "v1.0.0"])?;
+    let event = tempfile::NamedTempFile::new()?;
+    std::fs::write(event.path(), r#"{"deleted": true, "forced": false}"#)?;
+    let info = info_env(
+        &repo.repo,
+        &[
+            ("GITHUB_EVENT_NAME", "push"),
+            ("GITHUB_REF", "refs/tags/v1.0.0"),
+            ("GITHUB_EVENT_PATH", event.path().to_str().unwrap()),
+        ],
+    )?;
+    assert_eq!(info.is_push, Some(true));
+    assert_eq!(info.is_deleted, Some(true));
+    assert_eq!(info.is_forced, Some(false));
+    assert_eq!(info.is_push_tag, Some(false));
+    assert_eq!(info.is_tag_release, Some(false));
+    assert_eq!(info.version_tagged, None);
+    assert_eq!(info.version_docker_ci, "null");
     Ok(())
 }
+
+#[test]
+fn workflow_dispatch() -> Result<()> {
+    environ_reset();
+    let repo = TmpGit::new()?;
+    repo.file_write(
+        "Cargo.toml",
+        "[package]\nname = \"back\"\nversion = \"1.2.0\"\n",
+    )?;
+    repo.run(&["git", "add", "Cargo.toml"])?;
+    repo.run(&["git", "commit", "-m", "first commit"])?;
+    repo.run(&["git", "tag", "v1.1.0"])?;
+    let event = tempfile::NamedTempFile::new()?;
+    let info_get = |input: &str, payload: &str| -> Result<Info> {
+        std::fs::write(event.path(), payload)?;
+        info_env(
+            &repo.repo,
+            &[
+                ("GITHUB_EVENT_NAME", "workflow_dispatch"),
+                ("GITHUB_REF", "refs/heads/main"),
+                ("GITHUB_EVENT_PATH", event.path().to_str().unwrap()),
+                ("VERSION_GEN_DISPATCH_INPUT", input),
+            ],
+        )
+    };
+    let info = info_get("release", r#"{"inputs": {"release": "v1.2.0"}}"#)?;
+    assert_eq!(info.is_workflow_dispatch, Some(true));
+    assert_eq!(info.is_tag_release, Some(true));
+    assert_eq!(info.dispatch_version, Some("1.2.0".to_string()));
+    assert_eq!(info.version_tagged, Some("1.2.0".to_string()));
+    assert_eq!(info.version_commit, Some("1.2.0".to_string()));
+    assert_eq!(info.version_docker_ci, "1.2.0");
+    assert_eq!(info.version_mismatch, None);
+    let info = info_get("release", r#"{"inputs": {"release": "1.3.0"}}"#)?;
+    assert_eq!(
+        info.version_mismatch,
+        Some("Version mismatch: input 1.3.0 != 1.2.0 from Cargo.toml".to_string())
+    );
+    // Empty input, regular run:
+    let info = info_get("release", r#"{"inputs": {"release": ""}}"#)?;
+    assert_eq!(info.is_tag_release, Some(false));
+    assert_eq!(info.version_tagged, None);
+    assert!(info_get("release", r#"{"inputs": {"release": "latest"}}"#).is_err());
+    Ok(())
+}
+
+#[test]
+fn release_branches() -> Result<()> {
+    environ_reset();
+    let repo = TmpGit::new()?;
+    repo.file_write("foo.txt", "Hello, world!")?;
+    repo.run(&["git", "add", "foo.txt"])?;
+    repo.run(&["git", "commit", "-m", "first commit"])?;
+    repo.run(&["git", "branch", "-m", "develop"])?;
+    repo.run(&["git", "tag", "v1.0.0"])?;
+    let commit1 = git::head_commit(&repo.repo)?;
+    repo.run(&["git", "checkout", "-b", "release/1.x"])?;
+    repo.file_write("bar.txt", "Fix")?;
+    repo.run(&["git", "add", "bar.txt"])?;
+    repo.run(&["git", "commit", "-m", "fix"])?;
+    repo.run(&["git", "tag", "v1.0.1"])?;
+    let info_get = |git_ref: &str| {
+        info_env(
+            &repo.repo,
+            &[
+                ("GITHUB_EVENT_NAME", "push"),
+                ("GITHUB_REF", git_ref),
+                ("VERSION_GEN_MAIN_BRANCHES", "develop"),
+                ("VERSION_GEN_RELEASE_BRANCHES", "release/*"),
+            ],
+        )
+    };
+    // Tag on the release branch, not on the trunk:
+    let info = info_get("refs/tags/v1.0.1")?;
+    assert_eq!(info.commit_main, Some(commit1.clone()));
+    assert_eq!(info.is_main_here, Some(false));
+    assert_eq!(info.is_release_branch_here, Some(true));
+    assert_eq!(info.version_tagged, Some("1.0.1".to_string()));
+    assert_eq!(info.version_mismatch, None);
+    // Push to the release branch:
+    repo.file_write("bar.txt", "Another fix")?;
+    repo.run(&["git", "commit", "-am", "another fix"])?;
+    let info = info_get("refs/heads/release/1.x")?;
+    assert_eq!(info.is_main, Some(false));
+    assert_eq!(info.is_release_branch, Some(true));
+    assert_eq!(info.is_push_release_branch, Some(true));
+    assert_eq!(info.version_commit, Some("1.0.1-1".to_string()));
+    assert_eq!(info.version_docker_ci, "null");
+    // Push to the trunk:
+    repo.run(&["git", "checkout", "develop"])?;
+    repo.file_write("baz.txt", "Feature")?;
+    repo.run(&["git", "add", "baz.txt"])?;
+    repo.run(&["git", "commit", "-m", "feature"])?;
+    let info = info_get("refs/heads/develop")?;
+    assert_eq!(info.is_main, Some(true));
+    assert_eq!(info.is_release_branch, Some(false));
+    assert_eq!(info.is_main_here, Some(true));
+    assert_eq!(info.version_commit, Some("1.0.0-1".to_string()));
+    assert_eq!(info.version_docker_ci, "latest");
+    Ok(())
+}
+
+#[test]
+fn config_file() -> Result<()> {
+    environ_reset();
+    let config = Config::parse(
+        r#"
+        on-mismatch = "warn"
+        [tags]
+        include = ["v*"]
+        versions-only = false
+        [branches]
+        main = ["develop"]
+        [outputs]
+        only = ["tag_latest", "version_tagged"]
+        "#,
+    )?;
+    assert_eq!(config.tags.include, Some(vec!["v*".to_string()]));
+    assert_eq!(config.branches.main, Some(vec!["develop".to_string()]));
+    assert_eq!(config.on_mismatch, MismatchPolicy::Warn);
+    let e = Config::parse("[tags]\nincluded = [\"v*\"]\n").unwrap_err();
+    assert!(e.to_string().contains("unknown field `included`"), "{e}");
+    assert!(Config::parse("on-mismatch = \"explode\"\n").is_err());
+    // Config in the repository:
+    let repo = TmpGit::new()?;
+    std::fs::create_dir_all(repo.repo.path().join(".github"))?;
+    repo.file_write(".github/version-gen.toml", "[tags]\ninclude = [\"v*\"]\n")?;
+    repo.file_write(
+        "Cargo.toml",
+        "[package]\nname = \"back\"\nversion = \"0.1.0\"\n",
+    )?;
+    repo.run(&["git", "add", "."])?;
+    repo.run(&["git", "commit", "-m", "first commit"])?;
+    repo.run(&["git", "tag", "v1.0.0"])?;
+    repo.run(&["git", "tag", "2.0.0"])?;
+    let output = tempfile::NamedTempFile::new()?;
+    let args = Args {
+        event_name: Some("push".into()),
+        git_ref: Some("refs/tags/v1.0.0".into()),
+        github_output: Some(output.path().into()),
+        quiet: true,
+        only: vec!["tag_latest".into()],
+        ..repo.args()
+    };
+    // The version of Cargo.toml doesn't match:
+    assert!(ghaction_version_gen::process_repo(&args).is_err());
+    let contents = std::fs::read_to_string(output.path())?;
+    assert_eq!(contents, "tag_latest=v1.0.0\n");
+    // Config given in the command line, replacing the one in the
+    // repository:
+    let config = tempfile::NamedTempFile::new()?;
+    std::fs::write(
+        config.path(),
+        "on-mismatch = \"warn\"\n[tags]\ninclude = [\"v*\"]\n[outputs]\nonly = [\"version_tagged\"]\n",
+    )?;
+    let args = Args {
+        config: Some(config.path().into()),
+        only: vec![],
+        ..args
+    };
+    ghaction_version_gen::process_repo(&args)?;
+    let contents = std::fs::read_to_string(output.path())?;
+    assert_eq!(contents, "tag_latest=v1.0.0\nversion_tagged=1.0.0\n");
+    let args = Args {
+        config: Some(repo.repo.path().join("missing.toml")),
+        ..args
+    };
+    assert!(ghaction_version_gen::process_repo(&args).is_err());
+    Ok(())
+}
+
+#[test]
+fn gitlab() -> Result<()> {
+    environ_reset();
+    let repo = TmpGit::new()?;
+    repo.file_write("foo.txt", "Hello, world!")?;
+    repo.run(&["git", "add", "foo.txt"])?;
+    repo.run(&["git", "commit", "-m", "first commit"])?;
+    repo.run(&["git", "branch", "-m", "trunk"])?;
+    repo.run(&["git", "tag", "v1.0.0"])?;
+    let vars = |vars: &[(&str, &str)]| env_vars(vars).collect::<Vec<_>>();
+    let tag = vars(&[
+        ("GITLAB_CI", "true"),
+        ("CI_PIPELINE_SOURCE", "push"),
+        ("CI_COMMIT_TAG", "v1.0.0"),
+        ("CI_DEFAULT_BRANCH", "trunk"),
+    ]);
+    let provider = ci::Provider::detect(&tag);
+    assert_eq!(provider, ci::Provider::GitLab);
+    assert_eq!(ci::Provider::detect(&[]), ci::Provider::GitHub);
+    let info = Info::from_workspace(
+        &repo.repo,
+        &Config::default(),
+        provider.github_vars(&tag).into_iter(),
+    )?;
+    assert_eq!(info.is_push_tag, Some(true));
+    assert_eq!(info.version_tagged, Some("1.0.0".to_string()));
+    assert_eq!(info.main_branches, vec!["trunk"]);
+    assert_eq!(info.is_main_here, Some(true));
+    let branch = vars(&[
+        ("GITLAB_CI", "true"),
+        ("CI_PIPELINE_SOURCE", "push"),
+        ("CI_COMMIT_BRANCH", "trunk"),
+        ("CI_DEFAULT_BRANCH", "trunk"),
+    ]);
+    let info = Info::from_workspace(
+        &repo.repo,
+        &Config::default(),
+        provider.github_vars(&branch).into_iter(),
+    )?;
+    assert_eq!(info.is_push_main, Some(true));
+    assert_eq!(info.version_docker_ci, "latest");
+    let mr = vars(&[
+        ("GITLAB_CI", "true"),
+        ("CI_PIPELINE_SOURCE", "merge_request_event"),
+        ("CI_MERGE_REQUEST_IID", "7"),
+        ("CI_MERGE_REQUEST_SOURCE_BRANCH_NAME", "feature"),
+        ("CI_MERGE_REQUEST_TARGET_BRANCH_NAME", "trunk"),
+    ]);
+    let info = Info::from_workspace(
+        &repo.repo,
+        &Config::default(),
+        provider.github_vars(&mr).into_iter(),
+    )?;
+    assert_eq!(info.is_pull_request, Some(true));
+    assert_eq!(info.pr_number, Some("7".to_string()));
+    assert_eq!(info.pr_head_ref, Some("feature".to_string()));
+    assert_eq!(info.version_pr, Some("1.0.0-pr.7.0".to_string()));
+    let annotation = Annotation {
+        file: Some("Cargo.toml".into()),
+        position: Some(Position { line: 3, col: 1 }),
+        title: "Version mismatch".into(),
+        message: "tag 1.0.0 != 0.1.0 from Cargo.toml".into(),
+    };
+    assert_eq!(
+        provider.annotation(&annotation, "error"),
+        "Cargo.toml:3:1: error: Version mismatch: tag 1.0.0 != 0.1.0 from Cargo.toml"
+    );
+    // dotenv report:
+    let dotenv = tempfile::NamedTempFile::new()?;
+    let args = Args {
+        event_name: Some("push".into()),
+        git_ref: Some("refs/tags/v1.0.0".into()),
+        no_github_output: true,
+        dotenv: Some(dotenv.path().into()),
+        main_branch: vec!["trunk".into()],
+        quiet: true,
+        only: vec!["version_tagged".into(), "docker_tags_lines".into()],
+        ..repo.args()
+    };
+    ghaction_version_gen::process_repo(&args)?;
+    let contents = std::fs::read_to_string(dotenv.path())?;
+    assert_eq!(contents, "version_tagged=1.0.0\n");
+    // Only the main outputs by default:
+    let args = Args {
+        only: vec![],
+        ..args
+    };
+    ghaction_version_gen::process_repo(&args)?;
+    let contents = std::fs::read_to_string(dotenv.path())?;
+    let names = contents
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(k, _)| k)
+        .collect::<Vec<_>>();
+    assert!(names.contains(&"version_tagged"));
+    assert!(names.contains(&"version_docker_ci"));
+    assert!(names.contains(&"commit"));
+    assert!(!names.contains(&"json"));
+    assert!(!names.contains(&"git_describe_tags"));
+    assert!(
+        names
+            .iter()
+            .all(|k| ghaction_version_gen::DOTENV_DEFAULT.contains(k))
+    );
+    // The json and the outputs that are not valid variable names are
+    // never written:
+    let outputs = [
+        ("rust_crate_version".to_string(), "1.0.0"),
+        ("rust_crate_version_my-crate".to_string(), "1.0.0"),
+        ("json".to_string(), "{}"),
+    ];
+    ghaction_version_gen::write_dotenv(dotenv.path(), outputs.into_iter())?;
+    let contents = std::fs::read_to_string(dotenv.path())?;
+    assert_eq!(contents, "rust_crate_version=1.0.0\n");
+    Ok(())
+}
+
+#[test]
+fn local_mode() -> Result<()> {
+    environ_reset();
+    let repo = TmpGit::new()?;
+    repo.file_write("foo.txt", "Hello, world!")?;
+    repo.run(&["git", "add", "foo.txt"])?;
+    repo.run(&["git", "commit", "-m", "first commit"])?;
+    repo.run(&["git", "tag", "v1.0.0"])?;
+    repo.file_write("bar.txt", "Hello again!")?;
+    repo.run(&["git", "add", "bar.txt"])?;
+    repo.run(&["git", "commit", "-m", "second commit"])?;
+    let info_get = |vars: &[(&str, &str)]| {
+        let env = iter::once(("VERSION_GEN_LOCAL_MODE", "true"))
+            .chain(vars.iter().copied())
+            .collect::<Vec<_>>();
+        info_env(&repo.repo, &env)
+    };
+    // On the trunk:
+    let info = info_get(&[])?;
+    assert_eq!(info.is_push_main, Some(true));
+    assert_eq!(info.is_push_tag, Some(false));
+    assert_eq!(info.version_commit, Some("1.0.0-1".to_string()));
+    assert_eq!(info.version_docker_ci, "latest");
+    // Explicit ref:
+    let info = info_get(&[("GITHUB_REF", "refs/heads/feature")])?;
+    assert_eq!(info.is_push, Some(true));
+    assert_eq!(info.is_push_main, Some(false));
+    assert_eq!(info.version_docker_ci, "null");
+    // Detached at a tag:
+    repo.run(&["git", "checkout", "-q", "--detach", "v1.0.0"])?;
+    let info = info_get(&[])?;
+    assert_eq!(info.is_push_tag, Some(true));
+    assert_eq!(info.version_tagged, Some("1.0.0".to_string()));
+    // Detached elsewhere:
+    repo.run(&["git", "checkout", "-q", "--detach", "main"])?;
+    let info = info_get(&[])?;
+    assert_eq!(info.is_push, Some(true));
+    assert_eq!(info.is_tag, None);
+    assert_eq!(info.version_tagged, None);
+    assert_eq!(info.version_commit, None);
+    // Local mode enabled by the environment also ignores the github
+    // files; the binary is used as the process environment is read:
+    let output = tempfile::NamedTempFile::new()?;
+    let summary = tempfile::NamedTempFile::new()?;
+    let result = Command::new(env!("CARGO_BIN_EXE_ghaction-version-gen"))
+        .arg("-C")
+        .arg(repo.repo.path())
+        .env("VERSION_GEN_LOCAL_MODE", "true")
+        .env("GITHUB_OUTPUT", output.path())
+        .env("GITHUB_STEP_SUMMARY", summary.path())
+        .output()?;
+    ensure!(result.status.success(), "error running the binary");
+    assert_eq!(std::fs::read_to_string(output.path())?, "");
+    assert_eq!(std::fs::read_to_string(summary.path())?, "");
+    Ok(())
+}
+
+/// First source of the given kind, the one used for its outputs
+fn primary_source<S: VersionSource>(repo: &Path) -> Result<Option<S>> {
+    Ok(S::detect(repo)?.into_iter().next())
+}
+
+/// Path relative to the current directory, starting with `./`
+fn relative_path(path: &Path) -> Result<PathBuf> {
+    let mut relative = PathBuf::from(".");
+    for _ in env::current_dir()?.components().skip(1) {
+        relative.push("..");
+    }
+    Ok(relative.join(path.strip_prefix("/")?))
+}
+
+/// Shallow clone of the repository, as done by actions/checkout
+fn shallow_clone(origin: &TmpGit, depth: usize) -> Result<tempfile::TempDir> {
+    let clone = tempfile::tempdir()?;
+    let url = format!("file://{}", origin.repo.path().display());
+    let depth = format!("--depth={depth}");
+    let clone_path = clone.path().display().to_string();
+    let args = ["git", "clone", "-q", "--no-tags", &depth, &url, &clone_path];
+    origin.run(&args)?;
+    Ok(clone)
+}
+
+/// Number of commits in the history of HEAD
+fn history_len(repo: &Path) -> Result<usize> {
+    let output = Command::new("git")
+        .current_dir(repo)
+        .args(["rev-list", "--count", "HEAD"])
+        .output()?;
+    ensure!(output.status.success(), "error running git rev-list");
+    Ok(String::from_utf8(output.stdout)?.trim().parse()?)
+}
+
+#[test]
+fn shallow_history() -> Result<()> {
+    environ_reset();
+    let origin = TmpGit::new()?;
+    for i in 1..=120 {
+        let message = format!("commit {i}");
+        origin.run(&["git", "commit", "-q", "--allow-empty", "-m", &message])?;
+        if i == 100 {
+            origin.run(&["git", "tag", "v1.0.0"])?;
+        }
+    }
+    let filter = TagFilter::default();
+    let main = vec!["main".to_string()];
+    // The tag is fetched, along with just enough history to reach it:
+    let clone = shallow_clone(&origin, 3)?;
+    assert!(git::describe(&clone, &filter).is_err());
+    git::fetch_history(&clone, &filter, &main, &[])?;
+    assert!(git::is_shallow(&clone)?);
+    assert!(git::describe(&clone, &filter)?.starts_with("v1.0.0-20-g"));
+    let info = info_env(&clone, &[])?;
+    assert_eq!(info.tag_latest, "v1.0.0");
+    assert!(info.warnings.is_empty());
+    // A clone that already reaches the tags keeps all its history, and
+    // the older tags are fetched without it:
+    origin.run(&["git", "tag", "-a", "-m", "v0.9.0", "v0.9.0", "HEAD~25"])?;
+    origin.run(&["git", "tag", "-a", "-m", "v0.1.0", "v0.1.0", "HEAD~115"])?;
+    let clone = shallow_clone(&origin, 50)?;
+    let info = info_env(&clone, &[])?;
+    assert_eq!(info.tag_latest, "v1.0.0");
+    assert_eq!(history_len(clone.path())?, 50);
+    assert!(git::ref_commit(&clone, "refs/tags/v0.9.0").is_ok());
+    assert!(git::ref_commit(&clone, "refs/tags/v0.1.0").is_ok());
+    origin.run(&["git", "tag", "-d", "v0.9.0", "v0.1.0"])?;
+    // Without a tag, the whole history is fetched:
+    origin.run(&["git", "tag", "-d", "v1.0.0"])?;
+    let clone = shallow_clone(&origin, 3)?;
+    git::fetch_history(&clone, &filter, &main, &[])?;
+    assert!(!git::is_shallow(&clone)?);
+    // Fetching problems are reported as warnings:
+    let clone = shallow_clone(&origin, 3)?;
+    let clone_path = clone.path().display().to_string();
+    origin.run(&["git", "-C", &clone_path, "remote", "remove", "origin"])?;
+    let info = info_env(&clone, &[])?;
+    assert_eq!(info.tag_status, Some(git::TagStatus::Shallow));
+    assert_eq!(info.warnings.len(), 2);
+    assert_eq!(info.warnings[0].title, "Could not fetch the git history");
+    assert_eq!(info.warnings[1].title, "No tag found in the shallow clone");
+    // Checkout of a tag, as done by actions/checkout in tag pushes; the
+    // tip of the main branch is fetched to compare with it:
+    origin.run(&["git", "tag", "v1.0.0"])?;
+    let clone = TmpGit::new()?;
+    let url = format!("file://{}", origin.repo.path().display());
+    clone.run(&["git", "remote", "add", "origin", &url])?;
+    let refspec = "+refs/tags/v1.0.0:refs/tags/v1.0.0";
+    clone.run(&["git", "fetch", "-q", "--depth=1", "origin", refspec])?;
+    clone.run(&["git", "checkout", "-q", "--detach", "v1.0.0"])?;
+    let info = info_env(
+        &clone.repo,
+        &[
+            ("GITHUB_EVENT_NAME", "push"),
+            ("GITHUB_REF", "refs/tags/v1.0.0"),
+        ],
+    )?;
+    assert_eq!(info.commit_main, Some(info.commit.clone()));
+    assert_eq!(info.is_main_here, Some(true));
+    assert_eq!(info.is_tag_release, Some(true));
+    assert_eq!(info.version_mismatch, None);
+    assert!(info.warnings.is_empty());
+    Ok(())
+}
+
+#[test]
+fn tag_status() -> Result<()> {
+    environ_reset();
+    let origin = TmpGit::new()?;
+    origin.run(&["git", "commit", "-q", "--allow-empty", "-m", "first"])?;
+    let info_get = |repo: &Path| info_env(repo, &[]);
+    // No tags at all:
+    let info = info_get(origin.repo.path())?;
+    assert_eq!(info.tag_status, Some(git::TagStatus::NoTags));
+    assert!(info.warnings.is_empty());
+    assert!(
+        info.into_iter()
+            .any(|(k, v)| k == "tag_status" && v == "no-tags")
+    );
+    // Tags in the remote only:
+    origin.run(&["git", "tag", "v1.0.0"])?;
+    let clone = tempfile::tempdir()?;
+    let url = format!("file://{}", origin.repo.path().display());
+    let clone_path = clone.path().display().to_string();
+    origin.run(&["git", "clone", "-q", "--no-tags", &url, &clone_path])?;
+    let info = info_get(clone.path())?;
+    assert_eq!(info.tag_status, Some(git::TagStatus::NotFetched));
+    assert_eq!(info.warnings[0].title, "Tags not fetched");
+    assert_eq!(info.tag_latest, "");
+    // The remote is not queried in local mode:
+    let info = info_env(clone.path(), &[("VERSION_GEN_LOCAL_MODE", "true")])?;
+    assert_eq!(info.tag_status, Some(git::TagStatus::NoTags));
+    // Found:
+    let info = info_get(origin.repo.path())?;
+    assert_eq!(info.tag_status, Some(git::TagStatus::Found));
+    // Not a repository:
+    let dir = tempfile::tempdir()?;
+    let info = info_get(dir.path())?;
+    assert_eq!(info.tag_status, Some(git::TagStatus::NoGit));
+    assert_eq!(info.commit, "");
+    assert_eq!(info.warnings[0].title, "Git not available");
+    Ok(())
+}
+
+#[test]
+fn packagejson() -> Result<()> {
+    environ_reset();
+    let repo = TmpGit::new()?;
+    assert_eq!(primary_source::<node::Package>(repo.repo.path())?, None);
+    repo.file_write("package.json", "{")?;
+    assert!(primary_source::<node::Package>(repo.repo.path()).is_err());
+    // Without a name, the package.json only configures the tooling:
+    repo.file_write("package.json", "{\"version\": \"1.0.0\"}")?;
+    assert_eq!(primary_source::<node::Package>(repo.repo.path())?, None);
+    repo.file_write(
+        "package.json",
+        "{\"private\": true, \"devDependencies\": {\"prettier\": \"3.0.0\"}}",
+    )?;
+    assert_eq!(primary_source::<node::Package>(repo.repo.path())?, None);
+    repo.file_write("package.json", "{\"name\": 1}")?;
+    assert!(primary_source::<node::Package>(repo.repo.path()).is_err());
+    repo.file_write(
+        "package.json",
+        "{\"name\": \"front\", \"version\": \"1.0.0\"}",
+    )?;
+    assert_eq!(
+        primary_source::<node::Package>(repo.repo.path())?,
+        Some(node::Package {
+            name: "front".to_string(),
+            version: Some("1.0.0".to_string()),
+            manifest: "package.json".into(),
+            private: false,
+            position: Some(Position { line: 1, col: 19 }),
+        })
+    );
+    Ok(())
+}
+
+#[test]
+fn gitrepo_node_workspace() -> Result<()> {
+    environ_reset();
+    let repo = TmpGit::new()?;
+    std::fs::create_dir_all(repo.repo.path().join("packages/ui"))?;
+    std::fs::create_dir_all(repo.repo.path().join("packages/demo"))?;
+    repo.file_write(
+        "package.json",
+        "{\"name\": \"monorepo\", \"private\": true, \"workspaces\": [\"packages/*\"]}",
+    )?;
+    repo.file_write(
+        "packages/ui/package.json",
+        "{\"name\": \"ui\", \"version\": \"2.0.0\"}",
+    )?;
+    repo.file_write(
+        "packages/demo/package.json",
+        "{\"name\": \"demo\", \"version\": \"0.0.1\", \"private\": true}",
+    )?;
+    // Files matched by the glob are not packages:
+    repo.file_write("packages/README.md", "# Packages")?;
+    repo.run(&["git", "add", "."])?;
+    repo.run(&["git", "commit", "-m", "first commit"])?;
+    repo.run(&["git", "tag", "v1.0.0"])?;
+    let mut info = repo.info_get()?;
+    info.parse_files(&repo.repo)?;
+    info.is_push = Some(true);
+    info.is_tag = Some(true);
+    info.is_main = Some(true);
+    info.eval()?;
+    assert_eq!(info.name, "monorepo");
+    let source = info.source("node_package").unwrap();
+    assert_eq!(source.name(), "monorepo");
+    assert_eq!(source.version(), None);
+    let outputs = (&info).into_iter().collect::<Vec<_>>();
+    assert!(outputs.contains(&("node_package_name".to_string(), "monorepo")));
+    assert!(outputs.contains(&("node_package_names".to_string(), "monorepo demo ui")));
+    assert!(outputs.contains(&("node_package_version_ui".to_string(), "2.0.0")));
+    // demo is private, so only ui mismatches:
+    assert_eq!(
+        info.version_mismatch,
+        Some("Version mismatch: tag 1.0.0 != 2.0.0 from packages/ui/package.json".to_string())
+    );
+    // Unnamed root, the first member is the primary package:
+    repo.file_write(
+        "package.json",
+        "{\"private\": true, \"workspaces\": [\"packages/*\"]}",
+    )?;
+    let workspace = node::workspace_data(&repo.repo)?.unwrap();
+    assert_eq!(workspace.packages.len(), 2);
+    let relative = relative_path(repo.repo.path())?;
+    assert_eq!(node::workspace_data(&relative)?.unwrap().packages.len(), 2);
+    assert_eq!(workspace.packages[0].name, "demo");
+    Ok(())
+}
+
+#[test]
+fn gitrepo_component() -> Result<()> {
+    environ_reset();
+    let repo = TmpGit::new()?;
+    std::fs::create_dir_all(repo.repo.path().join("api"))?;
+    std::fs::create_dir_all(repo.repo.path().join("tools/cli"))?;
+    repo.file_write(
+        "api/Cargo.toml",
+        "[package]\nname = \"api\"\nversion = \"1.4.0\"\n",
+    )?;
+    repo.file_write(
+        "tools/cli/Cargo.toml",
+        "[package]\nname = \"cli\"\nversion = \"0.9.1\"\n",
+    )?;
+    repo.run(&["git", "add", "."])?;
+    repo.run(&["git", "commit", "-m", "first commit"])?;
+    repo.run(&["git", "tag", "api/v1.4.0"])?;
+    repo.file_write("foo.txt", "Hello, world!")?;
+    repo.run(&["git", "add", "foo.txt"])?;
+    repo.run(&["git", "commit", "-m", "second commit"])?;
+    repo.run(&["git", "tag", "cli/v0.9.2"])?;
+    let env = |component| {
+        vec![
+            ("GITHUB_EVENT_NAME", "push"),
+            ("GITHUB_REF", "refs/tags/cli/v0.9.2"),
+            ("VERSION_GEN_COMPONENT", component),
+        ]
+    };
+    // Tag of another component:
+    let info = info_env(&repo.repo, &env("api"))?;
+    assert_eq!(info.is_push_tag, Some(false));
+    assert_eq!(info.tag_latest, "api/v1.4.0");
+    assert_eq!(info.tag_latest_ltrimv, Some("1.4.0".to_string()));
+    assert_eq!(info.tag_distance_ltrimv, Some("1.4.0-1".to_string()));
+    assert_eq!(info.name, "api");
+    assert_eq!(info.version_tagged, None);
+    // Tag of this component, in a subdirectory:
+    let mut env = env("cli");
+    env.push(("VERSION_GEN_COMPONENT_DIR", "tools/cli"));
+    let info = info_env(&repo.repo, &env)?;
+    assert_eq!(info.is_push_tag, Some(true));
+    assert_eq!(info.tag_head, Some("cli/v0.9.2".to_string()));
+    assert_eq!(info.tag_head_ltrimv, Some("0.9.2".to_string()));
+    assert_eq!(info.version_tagged, Some("0.9.2".to_string()));
+    assert_eq!(info.name, "cli");
+    assert_eq!(
+        info.version_mismatches[0].file.as_deref(),
+        Some(Path::new("tools/cli/Cargo.toml"))
+    );
+    assert_eq!(
+        info.version_mismatch,
+        Some("Version mismatch: tag 0.9.2 != 0.9.1 from tools/cli/Cargo.toml".to_string())
+    );
+    Ok(())
+}
+
+#[test]
+fn sources_precedence() -> Result<()> {
+    environ_reset();
+    let repo = TmpGit::new()?;
+    repo.file_write(
+        "package.json",
+        "{\"name\": \"front\", \"version\": \"1.0.0\"}",
+    )?;
+    repo.file_write(
+        "Cargo.toml",
+        "[package]\nname = \"back\"\nversion = \"1.0.0\"\n",
+    )?;
+    let sources = ghaction_version_gen::source::detect_all(&repo.repo)?;
+    let prefixes = sources.iter().map(|s| s.prefix()).collect::<Vec<_>>();
+    assert_eq!(prefixes, vec!["rust_crate", "node_package"]);
+    let mut info = Info::default();
+    info.parse_files(&repo.repo)?;
+    info.eval()?;
+    assert_eq!(info.name, "back");
+    let outputs = (&info).into_iter().collect::<Vec<_>>();
+    assert!(outputs.contains(&("rust_crate_name".to_string(), "back")));
+    assert!(outputs.contains(&("node_package_name".to_string(), "front")));
+    assert!(outputs.contains(&("node_package_version".to_string(), "1.0.0")));
+    Ok(())
+}
+
+#[test]
+fn gitrepo_multiple_mismatches() -> Result<()> {
+    environ_reset();
+    let repo = TmpGit::new()?;
+    repo.file_write("foo.txt", "Hello, world!")?;
+    repo.run(&["git", "add", "foo.txt"])?;
+    repo.run(&["git", "commit", "-m", "first commit"])?;
+    repo.run(&["git", "checkout", "-b", "devel"])?;
+    repo.file_write(
+        "Cargo.toml",
+        "[package]\nname = \"test\"\nversion = \"9.7\"\n",
+    )?;
+    repo.file_write("setup.cfg", "[metadata]\nname = test\nversion = 9.8\n")?;
+    repo.run(&["git", "add", "Cargo.toml", "setup.cfg"])?;
+    repo.run(&["git", "commit", "-m", "second commit"])?;
+    repo.run(&["git", "tag", "v1.0.0"])?;
+    let mut info = repo.info_get()?;
+    info.is_push = Some(true);
+    info.is_tag = Some(true);
+    info.eval()?;
+    assert_eq!(info.version_mismatches.len(), 3);
+    assert_eq!(info.version_mismatch_count, "3");
+    assert_eq!(
+        info.version_mismatches[0].command("error"),
+        "::error file=Cargo.toml,line=3,col=1,title=Version mismatch::tag 1.0.0 != 9.7 from Cargo.toml"
+    );
+    assert_eq!(
+        info.version_mismatches[1].command("warning"),
+        "::warning file=setup.cfg,line=3,col=1,title=Version mismatch::tag 1.0.0 != 9.8 from setup.cfg"
+    );
+    assert!(info.version_mismatches[2].message.contains("pushed over"));
+    assert_eq!(info.version_mismatches[2].file, None);
+    assert_eq!(
+        info.version_mismatch,
+        Some(format!(
+            "{}; {}; {}",
+            info.version_mismatches[0], info.version_mismatches[1], info.version_mismatches[2]
+        ))
+    );
+    // No mismatches when not pushing:
+    info.is_push = Some(false);
+    info.eval()?;
+    assert_eq!(info.version_mismatches.len(), 0);
+    assert_eq!(info.version_mismatch, None);
+    assert_eq!(info.version_mismatch_count, "0");
+    Ok(())
+}
+
+#[test]
+fn annotation() {
+    let annotation = Annotation {
+        file: Some("dir,1/Cargo.toml".into()),
+        position: Some(Position { line: 7, col: 3 }),
+        title: "Title: with, separators".into(),
+        message: "100% wrong\nreally".into(),
+    };
+    assert_eq!(
+        annotation.command("error"),
+        "::error file=dir%2C1/Cargo.toml,line=7,col=3,title=Title%3A with%2C separators::100%25 wrong%0Areally"
+    );
+    assert_eq!(
+        annotation.to_string(),
+        "Title: with, separators: 100% wrong\nreally"
+    );
+    let annotation = Annotation {
+        title: "Title".into(),
+        message: "message".into(),
+        ..Annotation::default()
+    };
+    assert_eq!(
+        annotation.command("warning"),
+        "::warning title=Title::message"
+    );
+}
+
+struct Backend {
+    describe: fn(&Path, &TagFilter) -> Result<String>,
+    ref_commit: fn(&Path, &str) -> Result<String>,
+    head_commit: fn(&Path) -> Result<String>,
+    head_branch: fn(&Path) -> Result<String>,
+    head_branches: fn(&Path) -> Result<Vec<String>>,
+    tags: fn(&Path, &TagFilter) -> Result<Vec<String>>,
+    is_shallow: fn(&Path) -> Result<bool>,
+}
+
+fn backend_check(backend: Backend) -> Result<()> {
+    let repo = TmpGit::new()?;
+    let path = repo.repo.path();
+    repo.file_write("foo.txt", "Hello, world!")?;
+    repo.run(&["git", "add", "foo.txt"])?;
+    repo.run(&["git", "commit", "-m", "first commit"])?;
+    let commit1 = (backend.head_commit)(path)?;
+    assert_eq!(commit1, git::command::head_commit(path)?);
+    assert_eq!((backend.ref_commit)(path, "refs/heads/main")?, commit1);
+    assert!((backend.ref_commit)(path, "refs/heads/master").is_err());
+    assert!((backend.describe)(path, &TagFilter::default()).is_err());
+    assert!(!(backend.is_shallow)(path)?);
+    repo.run(&["git", "tag", "v1.0.0"])?;
+    assert_eq!((backend.describe)(path, &TagFilter::default())?, "v1.0.0");
+    repo.file_write("bar.txt", "Hello again!")?;
+    repo.run(&["git", "add", "bar.txt"])?;
+    repo.run(&["git", "commit", "-m", "second commit"])?;
+    let commit2 = (backend.head_commit)(path)?;
+    assert_ne!(commit1, commit2);
+    assert_eq!(
+        (backend.describe)(path, &TagFilter::default())?,
+        format!("v1.0.0-1-g{commit2}")
+    );
+    repo.run(&["git", "tag", "-a", "v1.1.0", "-m", "annotated"])?;
+    assert_eq!((backend.describe)(path, &TagFilter::default())?, "v1.1.0");
+    assert_eq!((backend.ref_commit)(path, "v1.0.0")?, commit1);
+    repo.run(&["git", "branch", "old", &commit1])?;
+    repo.run(&["git", "branch", "release/1.x"])?;
+    repo.run(&[
+        "git",
+        "update-ref",
+        "refs/remotes/origin/release/1.x",
+        "HEAD",
+    ])?;
+    let mut branches = (backend.head_branches)(path)?;
+    branches.sort();
+    assert_eq!(branches, vec!["main", "release/1.x", "release/1.x"]);
+    assert_eq!((backend.head_branch)(path)?, "main");
+    repo.run(&["git", "checkout", "-q", "release/1.x"])?;
+    assert_eq!((backend.head_branch)(path)?, "release/1.x");
+    repo.run(&["git", "checkout", "-q", "--detach"])?;
+    assert!((backend.head_branch)(path).is_err());
+    // Tag filtering:
+    repo.file_write("baz.txt", "Hello again again!")?;
+    repo.run(&["git", "add", "baz.txt"])?;
+    repo.run(&["git", "commit", "-m", "third commit"])?;
+    repo.run(&["git", "tag", "nightly"])?;
+    let commit3 = (backend.head_commit)(path)?;
+    let all_tags = TagFilter {
+        versions_only: false,
+        ..TagFilter::default()
+    };
+    assert_eq!((backend.describe)(path, &all_tags)?, "nightly");
+    let mut tags = (backend.tags)(path, &all_tags)?;
+    tags.sort();
+    assert_eq!(tags, vec!["nightly", "v1.0.0", "v1.1.0"]);
+    assert_eq!((backend.tags)(path, &TagFilter::default())?.len(), 2);
+    assert_eq!(
+        (backend.describe)(path, &TagFilter::default())?,
+        format!("v1.1.0-1-g{commit3}")
+    );
+    let include = TagFilter {
+        include: vec!["v1.0*".into()],
+        ..TagFilter::default()
+    };
+    assert_eq!(
+        (backend.describe)(path, &include)?,
+        format!("v1.0.0-2-g{commit3}")
+    );
+    let exclude = TagFilter {
+        exclude: vec!["v1.1*".into(), "nightly".into()],
+        versions_only: false,
+        ..TagFilter::default()
+    };
+    assert_eq!(
+        (backend.describe)(path, &exclude)?,
+        format!("v1.0.0-2-g{commit3}")
+    );
+    let nothing = TagFilter {
+        include: vec!["docs-*".into()],
+        ..TagFilter::default()
+    };
+    assert!((backend.describe)(path, &nothing).is_err());
+    // Several tags on the same commit, git picks the first lightweight
+    // one in alphabetical order, or the newest annotated one:
+    repo.run(&[
+        "git",
+        "commit",
+        "-q",
+        "--allow-empty",
+        "-m",
+        "fourth commit",
+    ])?;
+    repo.run(&["git", "tag", "v2.0.0-rc.1"])?;
+    repo.run(&["git", "tag", "v2.0.0"])?;
+    assert_eq!((backend.describe)(path, &TagFilter::default())?, "v2.0.0");
+    repo.run(&["git", "commit", "-q", "--allow-empty", "-m", "fifth commit"])?;
+    for (tag, date) in [
+        ("v3.0.0-rc.2", "1893542400 +0000"),
+        ("v3.0.0-rc.1", "1893456000 +0000"),
+    ] {
+        let status = Command::new("git")
+            .current_dir(path)
+            .env("GIT_COMMITTER_DATE", date)
+            .args(["tag", "-a", tag, "-m", "annotated"])
+            .status()?;
+        ensure!(status.success(), "error running command");
+    }
+    repo.run(&["git", "tag", "v3.0.0"])?;
+    assert_eq!(
+        (backend.describe)(path, &TagFilter::default())?,
+        "v3.0.0-rc.2"
+    );
+    Ok(())
+}
+
+#[test]
+fn backend_command() -> Result<()> {
+    backend_check(Backend {
+        describe: |p, f| git::command::describe(p, f),
+        ref_commit: |p, r| git::command::ref_commit(p, r),
+        head_commit: |p| git::command::head_commit(p),
+        head_branch: |p| git::command::head_branch(p),
+        head_branches: |p| git::command::head_branches(p),
+        tags: |p, f| git::command::tags(p, f),
+        is_shallow: |p| git::command::is_shallow(p),
+    })
+}
+
+#[cfg(feature = "gix")]
+#[test]
+fn backend_native() -> Result<()> {
+    backend_check(Backend {
+        describe: |p, f| git::native::describe(p, f),
+        ref_commit: |p, r| git::native::ref_commit(p, r),
+        head_commit: |p| git::native::head_commit(p),
+        head_branch: |p| git::native::head_branch(p),
+        head_branches: |p| git::native::head_branches(p),
+        tags: |p, f| git::native::tags(p, f),
+        is_shallow: |p| git::native::is_shallow(p),
+    })
+}
+
+/// Info with the settings from the given environment variables
+fn info_from_env(vars: &[(&str, &str)]) -> Info {
+    let mut info = Info::default();
+    info.parse_env(env_vars(vars));
+    info
+}
+
+#[test]
+fn env_settings() {
+    // Only the variables with the prefix are settings:
+    let info = info_from_env(&[("TAG_INCLUDE", "v*"), ("TAG_VERSIONS_ONLY", "false")]);
+    assert_eq!(info.tag_filter, TagFilter::default());
+    let info = info_from_env(&[
+        ("VERSION_GEN_TAG_INCLUDE", "v*"),
+        ("VERSION_GEN_TAG_EXCLUDE", "*-rc*"),
+        ("VERSION_GEN_TAG_VERSIONS_ONLY", "false"),
+    ]);
+    assert_eq!(info.tag_filter.include, vec!["v*"]);
+    assert_eq!(info.tag_filter.exclude, vec!["*-rc*"]);
+    assert!(!info.tag_filter.versions_only);
+    let info = info_from_env(&[("COMPONENT", "api"), ("COMPONENT_DIR", "services/api")]);
+    assert_eq!(info.component, None);
+    assert_eq!(info.tag_filter.prefix, "");
+    let info = info_from_env(&[
+        ("VERSION_GEN_COMPONENT", "api"),
+        ("VERSION_GEN_COMPONENT_DIR", "services/api"),
+    ]);
+    assert_eq!(info.component, Some("api".to_string()));
+    assert_eq!(info.tag_filter.prefix, "api/");
+    assert_eq!(info.manifest_dir, std::path::Path::new("services/api"));
+    let info = info_from_env(&[("DISPATCH_INPUT", "release")]);
+    assert_eq!(info.dispatch_input, None);
+    let info = info_from_env(&[("VERSION_GEN_DISPATCH_INPUT", "release")]);
+    assert_eq!(info.dispatch_input, Some("release".to_string()));
+    let info = info_from_env(&[
+        ("MAIN_BRANCHES", "develop"),
+        ("RELEASE_BRANCHES", "release/*"),
+    ]);
+    assert!(!info.main_branches.contains(&"develop".to_string()));
+    assert!(info.release_branches.is_empty());
+    let info = info_from_env(&[
+        ("VERSION_GEN_MAIN_BRANCHES", "develop"),
+        ("VERSION_GEN_RELEASE_BRANCHES", "release/*"),
+    ]);
+    assert_eq!(info.main_branches, vec!["develop"]);
+    assert_eq!(info.release_branches, vec!["release/*"]);
+    let info = info_from_env(&[("DEFAULT_BRANCH", "trunk")]);
+    assert_eq!(info.default_branch, None);
+    let info = info_from_env(&[("VERSION_GEN_DEFAULT_BRANCH", "trunk")]);
+    assert_eq!(info.default_branch, Some("trunk".to_string()));
+    assert_eq!(info.main_branches, vec!["trunk"]);
+    let info = info_from_env(&[("LOCAL_MODE", "true")]);
+    assert!(!info.local);
+    let info = info_from_env(&[("VERSION_GEN_LOCAL_MODE", "true")]);
+    assert!(info.local);
+}
+
+#[test]
+fn tag_filter() {
+    assert_eq!(
+        git::branch_name("refs/heads/release/1.x"),
+        Some("release/1.x")
+    );
+    assert_eq!(git::branch_name("refs/remotes/origin/main"), Some("main"));
+    assert_eq!(git::branch_name("refs/remotes/origin/HEAD"), None);
+    assert_eq!(git::branch_name("refs/tags/v1.0"), None);
+    assert!(git::looks_like_version("v1.2.3"));
+    assert!(git::looks_like_version("7.5"));
+    assert!(git::looks_like_version("2"));
+    assert!(git::looks_like_version("1.0.0-rc.1+build.5"));
+    assert!(!git::looks_like_version("nightly"));
+    assert!(!git::looks_like_version("docs-1.0"));
+    assert!(!git::looks_like_version("v"));
+    let filter = TagFilter {
+        prefix: String::new(),
+        include: vec!["v*".into()],
+        exclude: vec!["*-rc*".into()],
+        versions_only: true,
+    };
+    assert!(filter.matches("v1.0"));
+    assert!(!filter.matches("1.0"));
+    assert!(!filter.matches("v1.0-rc1"));
+    assert!(!filter.matches("vnext"));
+    let filter = TagFilter {
+        prefix: "api/".into(),
+        ..TagFilter::default()
+    };
+    assert!(filter.matches("api/v1.4.0"));
+    assert!(!filter.matches("v1.4.0"));
+    assert!(!filter.matches("cli/v1.4.0"));
+    assert!(!filter.matches("api/nightly"));
+}
+
+#[test]
+fn version_parse() {
+    assert_eq!(
+        Version::parse("1.2.3-rc.1+build.5"),
+        Some(Version {
+            major: 1,
+            minor: Some(2),
+            patch: Some(3),
+            prerelease: Some("rc.1".to_string()),
+            build: Some("build.5".to_string()),
+            is_semver: true,
+        })
+    );
+    assert_eq!(
+        Version::parse("7.5"),
+        Some(Version {
+            major: 7,
+            minor: Some(5),
+            patch: None,
+            prerelease: None,
+            build: None,
+            is_semver: false,
+        })
+    );
+    assert_eq!(Version::parse("1.02.3").map(|v| v.is_semver), Some(false));
+    assert_eq!(
+        Version::parse("2-beta").map(|v| v.prerelease),
+        Some(Some("beta".to_string()))
+    );
+    assert_eq!(Version::parse("nightly"), None);
+    let mut info = Info::default();
+    info.parse_describe("v2.0.0-beta.1-3-gc5f7a99").unwrap();
+    info.eval().unwrap();
+    let outputs = (&info).into_iter().collect::<Vec<_>>();
+    assert!(outputs.contains(&("version_major".to_string(), "2")));
+    assert!(outputs.contains(&("version_prerelease".to_string(), "beta.1")));
+    assert!(outputs.contains(&("version_is_semver".to_string(), "true")));
+    assert!(!outputs.iter().any(|(k, _)| k == "version_build"));
+}